@@ -0,0 +1,201 @@
+//! Keyframed animation of one property of a layer, for rendering short
+//! (looping) clips offline; see [`video`](crate::video) for the encoders.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Easing, SchotterParams};
+
+/// What an [`Animation`] changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Property {
+    /// The layer's `disp_adj`.
+    #[default]
+    Displacement,
+    /// The layer's `rot_adj`.
+    Rotation,
+    /// The layer's `noise_phase`.
+    NoisePhase,
+}
+
+impl Property {
+    pub const ALL: [Property; 3] = [
+        Property::Displacement,
+        Property::Rotation,
+        Property::NoisePhase,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Property::Displacement => "displacement",
+            Property::Rotation => "rotation",
+            Property::NoisePhase => "noise-phase",
+        }
+    }
+}
+
+impl FromStr for Property {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Property::ALL
+            .into_iter()
+            .find(|property| property.name() == s)
+            .ok_or_else(|| format!("unknown property: {}", s))
+    }
+}
+
+/// A value the property passes through, `time` seconds into the clip.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+}
+
+/// Written as `<TIME>:<VALUE>`.
+impl fmt::Display for Keyframe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.time, self.value)
+    }
+}
+
+impl FromStr for Keyframe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid keyframe: {}", s);
+        let (time, value) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(Keyframe {
+            time: time.trim().parse().map_err(|_| invalid())?,
+            value: value.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Parses keyframes separated by commas, like `0:0, 2:3`, sorted by time.
+pub fn parse_keyframes(s: &str) -> Result<Vec<Keyframe>, String> {
+    let mut keyframes = s
+        .split(',')
+        .filter(|keyframe| !keyframe.trim().is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<Keyframe>, _>>()?;
+    if keyframes.is_empty() {
+        return Err(String::from("no keyframes given"));
+    }
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(keyframes)
+}
+
+/// Formats keyframes the way [`parse_keyframes`] reads them.
+pub fn format_keyframes(keyframes: &[Keyframe]) -> String {
+    keyframes
+        .iter()
+        .map(|keyframe| keyframe.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Animation {
+    pub property: Property,
+    /// Sorted by time.
+    pub keyframes: Vec<Keyframe>,
+    /// How the value moves from one keyframe to the next.
+    pub easing: Easing,
+    /// The length of the clip, in seconds.
+    pub duration: f32,
+    /// Frames per second.
+    pub fps: f32,
+    /// Ease from the last keyframe back to the first by the end of the clip,
+    /// so that it plays seamlessly on repeat. Otherwise the last value holds.
+    pub looping: bool,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            property: Property::default(),
+            keyframes: vec![
+                Keyframe {
+                    time: 0.0,
+                    value: 0.0,
+                },
+                Keyframe {
+                    time: 2.0,
+                    value: 3.0,
+                },
+            ],
+            easing: Easing::default(),
+            duration: 4.0,
+            fps: 30.0,
+            looping: true,
+        }
+    }
+}
+
+impl Animation {
+    pub fn frame_count(&self) -> u32 {
+        (self.duration * self.fps).round().max(1.0) as u32
+    }
+
+    /// The value of the property `time` seconds in, eased between
+    /// keyframes.
+    pub fn value_at(&self, time: f32) -> f32 {
+        let (Some(first), Some(last)) = (self.keyframes.first(), self.keyframes.last()) else {
+            return 0.0;
+        };
+        let closing = Keyframe {
+            time: self.duration.max(last.time),
+            value: first.value,
+        };
+        let end = if self.looping { &closing } else { last };
+        if time <= first.time {
+            return first.value;
+        }
+        if time >= end.time {
+            return end.value;
+        }
+        let keyframes = self
+            .keyframes
+            .iter()
+            .chain(self.looping.then_some(&closing));
+        let mut previous = first;
+        for keyframe in keyframes {
+            if time < keyframe.time {
+                let t = (time - previous.time) / (keyframe.time - previous.time);
+                let t = self.easing.apply(t);
+                return previous.value + (keyframe.value - previous.value) * t;
+            }
+            previous = keyframe;
+        }
+        previous.value
+    }
+
+    /// `params` with the property of the layer at index `layer` set as it is
+    /// `time` seconds in.
+    pub fn at(&self, params: &SchotterParams, layer: usize, time: f32) -> SchotterParams {
+        let mut params = params.clone();
+        if let Some(layer) = params.layers.get_mut(layer) {
+            let value = self.value_at(time);
+            let schotter = &mut layer.schotter;
+            match self.property {
+                Property::Displacement => schotter.disp_adj = value.max(0.0),
+                Property::Rotation => schotter.rot_adj = value.max(0.0),
+                Property::NoisePhase => schotter.noise_phase = value,
+            }
+        }
+        params
+    }
+
+    /// Every frame of the clip, in order.
+    pub fn frames<'a>(
+        &'a self,
+        params: &'a SchotterParams,
+        layer: usize,
+    ) -> impl Iterator<Item = SchotterParams> + 'a {
+        (0..self.frame_count()).map(move |i| self.at(params, layer, i as f32 / self.fps))
+    }
+}
@@ -0,0 +1,357 @@
+//! Renders Schotter compositions from the command line, without a window.
+
+use std::{env, fs, process};
+
+use nannou_schotter::{
+    animation, pdf, plot, print, raster, shape, svg, video, Animation, Layer, Layout, Noise,
+    Palette, PlotSettings, PrintSettings, Schotter, SchotterParams, VideoFormat, MAX_GRID,
+};
+use rand::Rng;
+
+const USAGE: &str = "\
+Usage: schotter-cli [OPTIONS]
+
+Options:
+  --preset <PATH>     Start from a TOML/JSON preset or an exported PNG
+  --layer <N>         Layer of the preset that the following options change
+                      [default: 1]
+  --seed <N>          Random seed (random if omitted)
+  --disp <F>          Displacement factor [default: 1.0]
+  --rot <F>           Rotation factor [default: 1.0]
+  --noise <NOISE>     uniform, gaussian, perlin, simplex or value [default: uniform]
+  --noise-scale <F>   Frequency of coherent noise, up to 100 [default: 0.2]
+  --noise-phase <F>   Moves every stone through the noise at once; whole
+                      numbers step uniform and Gaussian noise to the next
+                      seeds. Up to a million either way [default: 0]
+  --sequential        Draw uniform and Gaussian noise from one stream, as
+                      versions without per-stone randomness did
+  --disp-falloff <CURVE>
+                      Displacement falloff [default: linear]
+  --rot-falloff <CURVE>
+                      Rotation falloff [default: linear]
+  --shapes <SHAPES>   Space separated shapes, e.g. 'square polygon:5'
+  --shape-mode <MODE> global, rows or columns [default: global]
+  --background <COLOR>
+                      Background color [default: #f5f5f5]
+  --stroke <COLOR>    Stroke color [default: #000000]
+  --fill <COLOR>      Fill color [default: #000000]
+  --fill-opacity <F>  Fill opacity, 0 for no fill [default: 0]
+  --color-mode <MODE> solid, row, column, displacement, rotation or random
+                      [default: solid]
+  --color-target <TARGET>
+                      What the color mode paints: stroke, fill or both
+                      [default: stroke]
+  --palette <PALETTE> A built-in palette name, or a .gpl, .ase, Lospec .json or
+                      hex list file to import
+  --colors <COLORS>   Comma separated colors to use instead of a palette
+  --cols <N>          Number of columns, up to 1000 [default: 12]
+  --rows <N>          Number of rows, up to 1000 [default: 22]
+  --size <PX>         Size of a stone in pixels [default: 30]
+  --dpi <DPI>         PNG resolution, taking --size and the margin to be
+                      pixels at 96 DPI [default: 96]
+  --occlusion <MODE>  none, earlier-on-top or later-on-top: clip the outlines
+                      hidden behind other stones [default: none]
+  --format <FORMAT>   svg, png, pdf, hpgl, gcode, gif, apng, mp4 or frames
+                      [default: from --out, else svg]
+  --out <PATH>        Output file [default: schotter-<seed>.<format>]
+  -h, --help          Print this help
+
+Paper options, for pdf, hpgl and gcode:
+  --paper <PAPER>     a4, a3, letter or a size in mm like 300x400 [default: a4]
+  --orientation <ORIENTATION>
+                      portrait or landscape [default: portrait]
+  --paper-margin <MM> Blank border around the drawing [default: 20 for pdf,
+                      15 for plotters]
+
+PDF options:
+  --batch <BATCH>     Pages to write: single, seeds:<COUNT>,
+                      displacement:<FROM>..<TO>:<STEPS> or
+                      rotation:<FROM>..<TO>:<STEPS>, varying the layer chosen
+                      with --layer [default: single]
+  --line-width-mm <MM>
+                      Stroke width [default: 0.5]
+  --bleed <MM>        Background beyond the trimmed page [default: 0]
+
+Animation options, for gif, apng, mp4 (through ffmpeg) and frames, a
+directory of numbered PNGs. The layer chosen with --layer is animated, and
+--dpi sets the frame size:
+  --animate <PROPERTY>
+                      displacement, rotation or noise-phase
+                      [default: displacement]
+  --keyframes <LIST>  Comma separated <SECONDS>:<VALUE> pairs [default: 0:0,2:3]
+  --easing <EASING>   linear, ease-in-out, elastic or bounce, between
+                      keyframes [default: ease-in-out]
+  --duration <SECONDS>
+                      Length of the clip [default: 4]
+  --fps <FPS>         Frames per second [default: 30]
+  --no-loop           Hold the last keyframe instead of easing back to the
+                      first by the end of the clip
+
+Plotter options, for hpgl and gcode:
+  --pen-up <GCODE>    Command that lifts the pen [default: 'G0 Z5']
+  --pen-down <GCODE>  Command that lowers the pen [default: 'G0 Z0']
+  --feed <MM/MIN>     Drawing speed [default: 1500]
+  --travel-feed <MM/MIN>
+                      Pen-up speed, G-code only [default: 3000]
+  --pens <N>          Pens the plotter holds [default: 8]
+  --pen-by <WHAT>     One pen per layer or per color [default: layer]
+  --no-optimize       Plot in row-major order instead of minimizing travel
+
+Falloff curves are linear, quadratic, exponential, sine, radial and column,
+each of which can be prefixed with inverted-, e.g. inverted-radial.
+
+Shapes are square, circle, triangle, hexagon, line, cross, polygon:<SIDES>
+with 3 to 1000 sides, and polyline:<X>,<Y>;<X>,<Y>;... with points in
+-0.5..0.5 and an optional trailing ;z to close the outline.
+
+Options are applied in order, so flags after --preset override its values.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Svg,
+    Png,
+    Hpgl,
+    Gcode,
+    Pdf,
+    Video(VideoFormat),
+}
+
+impl Format {
+    fn parse(s: &str) -> Option<Format> {
+        match s.to_ascii_lowercase().as_str() {
+            "svg" => Some(Format::Svg),
+            "png" => Some(Format::Png),
+            "hpgl" | "plt" => Some(Format::Hpgl),
+            "gcode" | "nc" => Some(Format::Gcode),
+            "pdf" => Some(Format::Pdf),
+            "gif" => Some(Format::Video(VideoFormat::Gif)),
+            "apng" => Some(Format::Video(VideoFormat::Apng)),
+            "mp4" => Some(Format::Video(VideoFormat::Mp4)),
+            "frames" => Some(Format::Video(VideoFormat::Frames)),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Svg => "svg",
+            Format::Png => "png",
+            Format::Hpgl => "hpgl",
+            Format::Gcode => "gcode",
+            Format::Pdf => "pdf",
+            Format::Video(format) => format.name(),
+        }
+    }
+}
+
+struct Args {
+    params: SchotterParams,
+    plot: PlotSettings,
+    print: PrintSettings,
+    /// The PDF pages to write.
+    pages: Vec<SchotterParams>,
+    animation: Animation,
+    /// The layer that options changed last, and that animations animate.
+    layer: usize,
+    dpi: f32,
+    format: Format,
+    out: String,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let seed = rand::thread_rng().gen_range(0..1_000_000);
+    let mut params = SchotterParams::new(&Schotter::new(seed), &Layout::default());
+    let mut layer = 0;
+    let mut plot = PlotSettings::default();
+    let mut print = PrintSettings::default();
+    let mut batch = pdf::Batch::default();
+    let mut animation = Animation::default();
+    let mut dpi = raster::BASE_DPI;
+    let mut format = None;
+    let mut out = None;
+
+    while let Some(arg) = args.next() {
+        let schotter = &mut params.layers[layer].schotter;
+        let layout = &mut params.layout;
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        if arg == "--sequential" {
+            schotter.sequential = true;
+            continue;
+        }
+        if arg == "--no-optimize" {
+            plot.optimize = false;
+            continue;
+        }
+        if arg == "--no-loop" {
+            animation.looping = false;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        let invalid = || format!("Invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--preset" => {
+                params =
+                    SchotterParams::load(&value).map_err(|err| format!("{}: {}", value, err))?;
+                if params.layers.is_empty() {
+                    params.layers.push(Layer::default());
+                }
+                layer = 0;
+            }
+            "--layer" => {
+                layer = match value.parse::<usize>() {
+                    Ok(n) if (1..=params.layers.len()).contains(&n) => n - 1,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--seed" => schotter.seed = value.parse().map_err(|_| invalid())?,
+            "--disp" => schotter.disp_adj = value.parse().map_err(|_| invalid())?,
+            "--rot" => schotter.rot_adj = value.parse().map_err(|_| invalid())?,
+            "--noise" => schotter.noise = value.parse()?,
+            "--noise-scale" => match value.parse::<f32>() {
+                Ok(scale) if scale.abs() <= Noise::MAX_SCALE => schotter.noise_scale = scale,
+                _ => return Err(invalid()),
+            },
+            "--noise-phase" => match value.parse::<f32>() {
+                Ok(phase) if phase.abs() <= Noise::MAX_PHASE => schotter.noise_phase = phase,
+                _ => return Err(invalid()),
+            },
+            "--disp-falloff" => schotter.disp_falloff = value.parse()?,
+            "--rot-falloff" => schotter.rot_falloff = value.parse()?,
+            "--shapes" => schotter.shapes = shape::parse_shapes(&value)?,
+            "--shape-mode" => schotter.shape_mode = value.parse()?,
+            "--background" => layout.background = value.parse()?,
+            "--stroke" => schotter.style.stroke = value.parse()?,
+            "--fill" => schotter.style.fill = value.parse()?,
+            "--fill-opacity" => {
+                schotter.style.fill_opacity = value.parse().map_err(|_| invalid())?
+            }
+            "--color-mode" => schotter.style.color_mode = value.parse()?,
+            "--color-target" => schotter.style.color_target = value.parse()?,
+            "--palette" => {
+                schotter.style.palette = match Palette::builtin()
+                    .into_iter()
+                    .find(|palette| palette.name.eq_ignore_ascii_case(&value))
+                {
+                    Some(palette) => palette,
+                    None => Palette::load(&value).map_err(|err| format!("{}: {}", value, err))?,
+                }
+            }
+            "--colors" => {
+                schotter.style.palette.name = String::from("Custom");
+                schotter.style.palette.colors =
+                    value.split(',').map(str::parse).collect::<Result<_, _>>()?
+            }
+            "--cols" => match value.parse() {
+                Ok(cols) if cols <= MAX_GRID => schotter.cols = cols,
+                _ => return Err(invalid()),
+            },
+            "--rows" => match value.parse() {
+                Ok(rows) if rows <= MAX_GRID => schotter.rows = rows,
+                _ => return Err(invalid()),
+            },
+            "--size" => layout.size = value.parse().map_err(|_| invalid())?,
+            "--dpi" => match value.parse() {
+                Ok(value) if value > 0.0 && value <= print::MAX_DPI => dpi = value,
+                _ => return Err(invalid()),
+            },
+            "--occlusion" => params.occlusion = value.parse()?,
+            "--paper" => {
+                plot.paper = value.parse()?;
+                print.paper = plot.paper;
+            }
+            "--orientation" => {
+                plot.orientation = value.parse()?;
+                print.orientation = plot.orientation;
+            }
+            "--paper-margin" => {
+                plot.margin = value.parse().map_err(|_| invalid())?;
+                print.margin = plot.margin;
+            }
+            "--batch" => batch = value.parse()?,
+            "--line-width-mm" => print.line_width = value.parse().map_err(|_| invalid())?,
+            "--bleed" => print.bleed = value.parse().map_err(|_| invalid())?,
+            "--animate" => animation.property = value.parse()?,
+            "--keyframes" => animation.keyframes = animation::parse_keyframes(&value)?,
+            "--easing" => animation.easing = value.parse()?,
+            "--duration" => animation.duration = value.parse().map_err(|_| invalid())?,
+            "--fps" => match value.parse() {
+                Ok(fps) if fps > 0.0 => animation.fps = fps,
+                _ => return Err(invalid()),
+            },
+            "--pen-up" => plot.pen_up = value,
+            "--pen-down" => plot.pen_down = value,
+            "--feed" => plot.feed_rate = value.parse().map_err(|_| invalid())?,
+            "--travel-feed" => plot.travel_rate = value.parse().map_err(|_| invalid())?,
+            "--pens" => plot.pens = value.parse().map_err(|_| invalid())?,
+            "--pen-by" => plot.pen_by = value.parse()?,
+            "--format" => format = Some(Format::parse(&value).ok_or_else(invalid)?),
+            "--out" => out = Some(value),
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    let format = format
+        .or_else(|| {
+            out.as_deref()
+                .and_then(|out| out.rsplit_once('.'))
+                .and_then(|(_, ext)| Format::parse(ext))
+        })
+        .unwrap_or(Format::Svg);
+    let seed = params.layers[0].schotter.seed;
+    let out = out.unwrap_or_else(|| format!("schotter-{}.{}", seed, format.extension()));
+
+    if format == Format::Pdf {
+        print.validate()?;
+    }
+    let pages = batch.pages(&params, layer);
+    Ok(Args {
+        params,
+        plot,
+        print,
+        pages,
+        animation,
+        layer,
+        dpi,
+        format,
+        out,
+    })
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = match args.format {
+        Format::Svg => svg::save(&args.out, &args.params),
+        Format::Png => raster::save(&args.out, &args.params, args.dpi),
+        Format::Hpgl => fs::write(&args.out, plot::to_hpgl(&args.params, &args.plot)),
+        Format::Gcode => fs::write(&args.out, plot::to_gcode(&args.params, &args.plot)),
+        Format::Pdf => pdf::save(&args.out, &args.pages, &args.print),
+        Format::Video(format) => video::save(
+            &args.out,
+            format,
+            &args.params,
+            args.layer,
+            &args.animation,
+            args.dpi,
+        ),
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to write {}: {}", args.out, err);
+        process::exit(1);
+    }
+    println!(
+        "{} (seed {})",
+        args.out, args.params.layers[0].schotter.seed
+    );
+}
@@ -0,0 +1,78 @@
+//! A hand-painted map of how far each stone strays, on top of the falloff.
+
+use serde::{Deserialize, Serialize};
+
+use crate::MAX_GRID;
+
+/// A multiplier of the displacement and rotation factors for every stone,
+/// 1 where nothing has been painted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChaosMap {
+    cols: u32,
+    rows: u32,
+    /// Row-major.
+    values: Vec<f32>,
+}
+
+impl ChaosMap {
+    /// The highest multiplier the brush paints.
+    pub const MAX: f32 = 3.0;
+
+    /// The multiplier of the stone at `col`, `row`.
+    pub fn get(&self, col: u32, row: u32) -> f32 {
+        if col >= self.cols || row >= self.rows {
+            return 1.0;
+        }
+        let index = row as usize * self.cols as usize + col as usize;
+        self.values.get(index).copied().unwrap_or(1.0)
+    }
+
+    /// Whether every multiplier is 1.
+    pub fn is_empty(&self) -> bool {
+        self.values.iter().all(|&value| value == 1.0)
+    }
+
+    pub fn clear(&mut self) {
+        *self = ChaosMap::default();
+    }
+
+    /// Paints a round brush of `radius` stones onto a `cols` by `rows` grid,
+    /// centered on `center` in grid units. Multipliers change by `strength`
+    /// at the center, fading smoothly to nothing at the edge; negative
+    /// strengths calm stones down. Grids larger than [`MAX_GRID`] are left
+    /// unpainted.
+    pub fn paint(&mut self, cols: u32, rows: u32, center: [f32; 2], radius: f32, strength: f32) {
+        if radius <= 0.0 || !self.resize(cols, rows) {
+            return;
+        }
+        for (i, value) in self.values.iter_mut().enumerate() {
+            let (col, row) = (i as u32 % cols, i as u32 / cols);
+            let dx = col as f32 + 0.5 - center[0];
+            let dy = row as f32 + 0.5 - center[1];
+            let t = 1.0 - (dx * dx + dy * dy).sqrt() / radius;
+            if t > 0.0 {
+                let weight = t * t * (3.0 - 2.0 * t);
+                *value = (*value + strength * weight).clamp(0.0, Self::MAX);
+            }
+        }
+    }
+
+    /// Fits the map to a `cols` by `rows` grid, keeping the stones that
+    /// both grids share. Returns whether the grid is small enough to fit.
+    fn resize(&mut self, cols: u32, rows: u32) -> bool {
+        if cols > MAX_GRID || rows > MAX_GRID {
+            return false;
+        }
+        let len = cols as usize * rows as usize;
+        if (self.cols, self.rows) == (cols, rows) && self.values.len() == len {
+            return true;
+        }
+        let values = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (col, row)))
+            .map(|(col, row)| self.get(col, row))
+            .collect();
+        *self = ChaosMap { cols, rows, values };
+        true
+    }
+}
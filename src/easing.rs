@@ -0,0 +1,113 @@
+//! Easing curves, shaping how motion speeds up and slows down.
+
+use std::{f32::consts::TAU, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    /// Cubic: slow at both ends.
+    #[default]
+    EaseInOut,
+    /// Overshoots and springs back before settling.
+    Elastic,
+    /// Arrives and bounces a few times.
+    Bounce,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseInOut,
+        Easing::Elastic,
+        Easing::Bounce,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::EaseInOut => "ease-in-out",
+            Easing::Elastic => "elastic",
+            Easing::Bounce => "bounce",
+        }
+    }
+
+    /// Maps progress `t` in `0.0..=1.0` to how far along the motion is. Every
+    /// curve starts at 0 and ends at 1, though elastic leaves `0.0..=1.0` in
+    /// between.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+            Easing::Elastic if t == 0.0 || t == 1.0 => t,
+            Easing::Elastic => 2f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * TAU / 3.0).sin() + 1.0,
+            Easing::Bounce => bounce(t),
+        }
+    }
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Easing::ALL
+            .into_iter()
+            .find(|easing| easing.name() == s)
+            .ok_or_else(|| format!("unknown easing: {}", s))
+    }
+}
+
+/// Four parabolas, each lower than the one before.
+fn bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    let (t, floor) = if t < 1.0 / D {
+        (t, 0.0)
+    } else if t < 2.0 / D {
+        (t - 1.5 / D, 0.75)
+    } else if t < 2.5 / D {
+        (t - 2.25 / D, 0.9375)
+    } else {
+        (t - 2.625 / D, 0.984375)
+    };
+    N * t * t + floor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_curve_starts_at_zero_and_ends_at_one() {
+        for easing in Easing::ALL {
+            assert_eq!(easing.apply(0.0), 0.0, "{}", easing.name());
+            assert_eq!(easing.apply(1.0), 1.0, "{}", easing.name());
+            assert_eq!(easing.apply(-0.5), 0.0, "{}", easing.name());
+            assert_eq!(easing.apply(1.5), 1.0, "{}", easing.name());
+        }
+    }
+
+    #[test]
+    fn smooth_curves_never_turn_back() {
+        for easing in [Easing::Linear, Easing::EaseInOut] {
+            let values: Vec<_> = (0..=100).map(|i| easing.apply(i as f32 / 100.0)).collect();
+            assert!(
+                values.windows(2).all(|pair| pair[0] <= pair[1]),
+                "{}",
+                easing.name()
+            );
+        }
+    }
+
+    #[test]
+    fn bounces_stay_below_the_end() {
+        for i in 0..=100 {
+            let value = Easing::Bounce.apply(i as f32 / 100.0);
+            assert!((0.0..=1.0).contains(&value), "{}", value);
+        }
+    }
+}
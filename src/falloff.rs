@@ -0,0 +1,108 @@
+//! Falloff curves: how strongly each stone is displaced or rotated.
+
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    /// Grows steadily from the top row to the bottom one, as in Nees' original.
+    #[default]
+    Linear,
+    Quadratic,
+    Exponential,
+    Sine,
+    /// Grows with the distance from the center of the grid.
+    Radial,
+    /// Grows from the left column to the right one.
+    Column,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 6] = [
+        Curve::Linear,
+        Curve::Quadratic,
+        Curve::Exponential,
+        Curve::Sine,
+        Curve::Radial,
+        Curve::Column,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Curve::Linear => "linear",
+            Curve::Quadratic => "quadratic",
+            Curve::Exponential => "exponential",
+            Curve::Sine => "sine",
+            Curve::Radial => "radial",
+            Curve::Column => "column",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Falloff {
+    pub curve: Curve,
+    /// Flips the curve, so the most disordered stones become the calmest.
+    pub inverted: bool,
+}
+
+impl Falloff {
+    pub fn new(curve: Curve) -> Self {
+        Falloff {
+            curve,
+            inverted: false,
+        }
+    }
+
+    /// The strength of the effect on the stone at `col`, `row` of a `cols` by
+    /// `rows` grid, roughly between 0 and 1.
+    pub fn factor(&self, col: u32, row: u32, cols: u32, rows: u32) -> f32 {
+        let t = row as f32 / rows as f32;
+        let factor = match self.curve {
+            Curve::Linear => t,
+            Curve::Quadratic => t * t,
+            Curve::Exponential => ((4.0 * t).exp() - 1.0) / (4.0f32.exp() - 1.0),
+            Curve::Sine => (t * PI / 2.0).sin(),
+            Curve::Radial => {
+                let dx = col as f32 + 0.5 - cols as f32 / 2.0;
+                let dy = row as f32 + 0.5 - rows as f32 / 2.0;
+                dx.hypot(dy) / (cols as f32 / 2.0).hypot(rows as f32 / 2.0)
+            }
+            Curve::Column => col as f32 / cols as f32,
+        };
+        if self.inverted {
+            1.0 - factor
+        } else {
+            factor
+        }
+    }
+}
+
+impl fmt::Display for Falloff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.inverted {
+            write!(f, "inverted-")?;
+        }
+        write!(f, "{}", self.curve.name())
+    }
+}
+
+/// Parses names like `quadratic` or `inverted-radial`.
+impl FromStr for Falloff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (inverted, name) = match s.strip_prefix("inverted-") {
+            Some(name) => (true, name),
+            None => (false, s),
+        };
+        let curve = Curve::ALL
+            .into_iter()
+            .find(|curve| curve.name() == name)
+            .ok_or_else(|| format!("unknown falloff: {}", s))?;
+        Ok(Falloff { curve, inverted })
+    }
+}
@@ -0,0 +1,190 @@
+//! Undo/redo history of compositions, and a list of starred favorites, kept
+//! in a file between sessions.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{params::Error, SchotterParams};
+
+/// The most states kept for undo; older ones are forgotten.
+pub const LIMIT: usize = 200;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    /// Oldest first.
+    states: Vec<SchotterParams>,
+    /// The index in `states` of the current composition. States after it can
+    /// be redone.
+    position: usize,
+    /// Newest first.
+    pub favorites: Vec<SchotterParams>,
+}
+
+impl History {
+    /// Every recorded state, oldest first.
+    pub fn states(&self) -> &[SchotterParams] {
+        &self.states
+    }
+
+    /// The index of the current state in [`History::states`].
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn current(&self) -> Option<&SchotterParams> {
+        self.states.get(self.position)
+    }
+
+    /// Makes `params` the current state, dropping any states that could have
+    /// been redone. Returns false, and does nothing, if it already is.
+    pub fn record(&mut self, params: &SchotterParams) -> bool {
+        if self.current() == Some(params) {
+            return false;
+        }
+        self.states.truncate(self.position + 1);
+        self.states.push(params.clone());
+        if self.states.len() > LIMIT {
+            self.states.drain(..self.states.len() - LIMIT);
+        }
+        self.position = self.states.len() - 1;
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position + 1 < self.states.len()
+    }
+
+    /// Steps back to the previous state and returns it.
+    pub fn undo(&mut self) -> Option<&SchotterParams> {
+        if !self.can_undo() {
+            return None;
+        }
+        self.go_to(self.position - 1)
+    }
+
+    /// Steps forward to the state last undone and returns it.
+    pub fn redo(&mut self) -> Option<&SchotterParams> {
+        if !self.can_redo() {
+            return None;
+        }
+        self.go_to(self.position + 1)
+    }
+
+    /// Makes the state at `index` current without forgetting any others.
+    pub fn go_to(&mut self, index: usize) -> Option<&SchotterParams> {
+        if index >= self.states.len() {
+            return None;
+        }
+        self.position = index;
+        self.current()
+    }
+
+    pub fn is_favorite(&self, params: &SchotterParams) -> bool {
+        self.favorites.contains(params)
+    }
+
+    /// Stars `params`, or unstars it if it is already a favorite.
+    pub fn toggle_favorite(&mut self, params: &SchotterParams) {
+        match self
+            .favorites
+            .iter()
+            .position(|favorite| favorite == params)
+        {
+            Some(i) => {
+                self.favorites.remove(i);
+            }
+            None => self.favorites.insert(0, params.clone()),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        Ok(fs::write(path, serde_json::to_string(self)?)?)
+    }
+
+    /// Reads a history written by [`History::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut history: History = serde_json::from_str(&fs::read_to_string(path)?)?;
+        history.position = history.position.min(history.states.len().saturating_sub(1));
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layout, Schotter};
+
+    fn with_seed(seed: u64) -> SchotterParams {
+        SchotterParams::new(&Schotter::new(seed), &Layout::default())
+    }
+
+    fn seed(params: Option<&SchotterParams>) -> Option<u64> {
+        params.map(|params| params.layers[0].schotter.seed)
+    }
+
+    #[test]
+    fn undo_and_redo_step_through_states() {
+        let mut history = History::default();
+        assert_eq!(seed(history.undo()), None);
+        for s in 0..3 {
+            assert!(history.record(&with_seed(s)));
+        }
+        assert!(!history.record(&with_seed(2)));
+        assert_eq!(history.states().len(), 3);
+
+        assert_eq!(seed(history.redo()), None);
+        assert_eq!(seed(history.undo()), Some(1));
+        assert_eq!(seed(history.undo()), Some(0));
+        assert_eq!(seed(history.undo()), None);
+        assert_eq!(seed(history.redo()), Some(1));
+        assert_eq!(seed(history.redo()), Some(2));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn recording_drops_the_states_to_redo() {
+        let mut history = History::default();
+        for s in 0..3 {
+            history.record(&with_seed(s));
+        }
+        history.undo();
+        history.undo();
+        assert!(history.record(&with_seed(7)));
+        assert!(!history.can_redo());
+        let seeds: Vec<_> = history.states().iter().map(|s| seed(Some(s))).collect();
+        assert_eq!(seeds, [Some(0), Some(7)]);
+        assert_eq!(history.position(), 1);
+    }
+
+    #[test]
+    fn favorites_toggle_newest_first() {
+        let mut history = History::default();
+        history.toggle_favorite(&with_seed(1));
+        history.toggle_favorite(&with_seed(2));
+        assert!(history.is_favorite(&with_seed(1)));
+        assert_eq!(history.favorites, [with_seed(2), with_seed(1)]);
+        history.toggle_favorite(&with_seed(1));
+        assert!(!history.is_favorite(&with_seed(1)));
+        assert_eq!(history.favorites, [with_seed(2)]);
+    }
+
+    #[test]
+    fn old_states_are_forgotten_past_the_limit() {
+        let mut history = History::default();
+        for s in 0..LIMIT as u64 + 50 {
+            history.record(&with_seed(s));
+        }
+        assert_eq!(history.states().len(), LIMIT);
+        assert_eq!(history.position(), LIMIT - 1);
+        assert_eq!(seed(history.states().first()), Some(50));
+        assert_eq!(seed(history.current()), Some(LIMIT as u64 + 49));
+        assert_eq!(seed(history.go_to(0)), Some(50));
+        assert!(!history.can_undo());
+    }
+}
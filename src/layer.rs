@@ -0,0 +1,100 @@
+//! Layers: several Schotter grids stacked into one composition.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Schotter;
+
+/// How a layer is composited onto the layers below it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Add,
+    Darken,
+    Lighten,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 6] = [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Add,
+        BlendMode::Darken,
+        BlendMode::Lighten,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "normal",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Add => "add",
+            BlendMode::Darken => "darken",
+            BlendMode::Lighten => "lighten",
+        }
+    }
+
+    /// The CSS `mix-blend-mode` with the same effect.
+    pub fn css(self) -> &'static str {
+        match self {
+            BlendMode::Add => "plus-lighter",
+            mode => mode.name(),
+        }
+    }
+}
+
+impl FromStr for BlendMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BlendMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| format!("unknown blend mode: {}", s))
+    }
+}
+
+/// One Schotter grid of a composition, and where and how it is drawn.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub blend: BlendMode,
+    /// Where the layer's top left corner sits, in grid units.
+    pub offset: [f32; 2],
+    #[serde(flatten)]
+    pub schotter: Schotter,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Layer::new(Schotter::default())
+    }
+}
+
+impl Layer {
+    pub fn new(schotter: Schotter) -> Self {
+        Layer {
+            name: String::from("Layer"),
+            visible: true,
+            blend: BlendMode::default(),
+            offset: [0.0, 0.0],
+            schotter,
+        }
+    }
+
+    /// The bottom right corner of the layer, in grid units.
+    pub fn extent(&self) -> [f32; 2] {
+        [
+            self.offset[0] + self.schotter.cols as f32,
+            self.offset[1] + self.schotter.rows as f32,
+        ]
+    }
+}
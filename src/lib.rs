@@ -0,0 +1,309 @@
+//! The Schotter generator, independent of any windowing or drawing backend.
+//!
+//! A [`Schotter`] describes a composition: a grid of squares that become more
+//! displaced and rotated towards the bottom, after Georg Nees' "Schotter".
+//! [`Schotter::stones`] turns it into a deterministic list of stone transforms,
+//! and [`Scene`] turns those into the outlines that the nannou app, the
+//! exporters and any other tool draw. A [`SchotterParams`] stacks several
+//! [`Layer`]s, each its own Schotter, into one composition.
+
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+pub mod animation;
+pub mod chaos;
+pub mod easing;
+pub mod falloff;
+pub mod history;
+pub mod layer;
+pub mod metadata;
+pub mod occlusion;
+pub mod overrides;
+pub mod palette;
+pub mod paper;
+pub mod params;
+pub mod pdf;
+pub mod plot;
+pub mod print;
+pub mod random;
+pub mod raster;
+pub mod scene;
+pub mod shape;
+pub mod style;
+pub mod svg;
+pub mod transition;
+pub mod video;
+
+pub use animation::Animation;
+pub use chaos::ChaosMap;
+pub use easing::Easing;
+pub use falloff::{Curve, Falloff};
+pub use history::History;
+pub use layer::{BlendMode, Layer};
+pub use occlusion::Occlusion;
+pub use overrides::StoneOverride;
+pub use palette::Palette;
+pub use paper::{Orientation, Paper};
+pub use params::SchotterParams;
+pub use plot::PlotSettings;
+pub use print::PrintSettings;
+pub use random::{Attribute, Noise, NoiseSource};
+pub use scene::Scene;
+pub use shape::{Path, ShapeMode, StoneShape};
+pub use style::{Color, ColorMode, ColorTarget, Paint, Style};
+pub use transition::{Stagger, Transition, TransitionSettings};
+pub use video::VideoFormat;
+
+pub const COLS: u32 = 12;
+pub const LINE_WIDTH: f32 = 0.06;
+pub const MARGIN: u32 = 35;
+pub const ROWS: u32 = 22;
+pub const SIZE: u32 = 30;
+/// The most columns or rows a grid may have.
+pub const MAX_GRID: u32 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schotter {
+    /// Written as a string when it is too large for a TOML integer.
+    #[serde(with = "params::seed")]
+    pub seed: u64,
+    pub cols: u32,
+    pub rows: u32,
+    pub disp_adj: f32,
+    pub rot_adj: f32,
+    pub noise: Noise,
+    /// How quickly coherent noise changes from one stone to the next.
+    pub noise_scale: f32,
+    /// Moves every stone through the noise at once. Animating it makes the
+    /// stones drift; see [`Noise::source`].
+    pub noise_phase: f32,
+    /// Draw uniform and Gaussian noise from one stream in row-major order,
+    /// so the look of a seed depends on the grid size. Presets saved before
+    /// per-stone hashing lack this field and load with it set.
+    #[serde(default = "legacy_sequential")]
+    pub sequential: bool,
+    pub shape_mode: ShapeMode,
+    /// The shapes to draw, cycled through per row or column by `shape_mode`.
+    pub shapes: Vec<StoneShape>,
+    pub disp_falloff: Falloff,
+    pub rot_falloff: Falloff,
+    pub style: Style,
+    /// Stones placed by hand; see [`Schotter::set_override`].
+    pub overrides: Vec<StoneOverride>,
+    /// Painted multipliers of both factors, on top of the falloffs.
+    pub chaos: ChaosMap,
+}
+
+impl Default for Schotter {
+    fn default() -> Self {
+        Schotter {
+            seed: 0,
+            cols: COLS,
+            rows: ROWS,
+            disp_adj: 1.0,
+            rot_adj: 1.0,
+            noise: Noise::default(),
+            noise_scale: 0.2,
+            noise_phase: 0.0,
+            sequential: false,
+            shape_mode: ShapeMode::default(),
+            shapes: vec![StoneShape::default()],
+            disp_falloff: Falloff::default(),
+            rot_falloff: Falloff::default(),
+            style: Style::default(),
+            overrides: Vec::new(),
+            chaos: ChaosMap::default(),
+        }
+    }
+}
+
+fn legacy_sequential() -> bool {
+    true
+}
+
+impl Schotter {
+    pub fn new(seed: u64) -> Self {
+        Schotter {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    /// Checks the values that a preset or the command line could set too
+    /// large to generate.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cols > MAX_GRID || self.rows > MAX_GRID {
+            return Err(format!(
+                "grid larger than {} x {}: {} x {}",
+                MAX_GRID, MAX_GRID, self.cols, self.rows
+            ));
+        }
+        // Written so that NaN fails too.
+        let scale_fits = self.noise_scale.abs() <= Noise::MAX_SCALE;
+        if !scale_fits {
+            return Err(format!(
+                "noise scale must be at most {}: {}",
+                Noise::MAX_SCALE,
+                self.noise_scale
+            ));
+        }
+        let phase_fits = self.noise_phase.abs() <= Noise::MAX_PHASE;
+        if !phase_fits {
+            return Err(format!(
+                "noise phase must be within ±{}: {}",
+                Noise::MAX_PHASE,
+                self.noise_phase
+            ));
+        }
+        Ok(())
+    }
+
+    /// Generates the stones in row-major order.
+    ///
+    /// The same parameters always produce the same stones: the random values
+    /// come from the noise source, seeded with `seed`, except where
+    /// `overrides` place a stone by hand.
+    pub fn stones(&self) -> Vec<Stone> {
+        let mut noise = self.noise.source(
+            self.seed,
+            self.noise_scale,
+            self.noise_phase,
+            self.sequential,
+        );
+        let mut stones = Vec::with_capacity(self.cols as usize * self.rows as usize);
+
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let chaos = self.chaos.get(col, row);
+                let disp_factor = self.disp_falloff.factor(col, row, self.cols, self.rows)
+                    * self.disp_adj
+                    * chaos;
+                let rot_factor =
+                    self.rot_falloff.factor(col, row, self.cols, self.rows) * self.rot_adj * chaos;
+                let mut stone = Stone {
+                    col,
+                    row,
+                    x: col as f32,
+                    y: row as f32,
+                    x_offset: disp_factor * noise.sample(col, row, Attribute::XOffset, 0.5),
+                    y_offset: disp_factor * noise.sample(col, row, Attribute::YOffset, 0.5),
+                    rotation: rot_factor * noise.sample(col, row, Attribute::Rotation, PI / 4.0),
+                };
+                // Sampled even so, to keep sequential noise in step.
+                if let Some(edit) = self.override_at(col, row) {
+                    edit.apply(&mut stone);
+                }
+                stones.push(stone);
+            }
+        }
+
+        stones
+    }
+
+    pub fn override_at(&self, col: u32, row: u32) -> Option<&StoneOverride> {
+        self.overrides
+            .iter()
+            .find(|edit| (edit.col, edit.row) == (col, row))
+    }
+
+    /// Places a stone by hand, replacing any earlier override of it.
+    pub fn set_override(&mut self, edit: StoneOverride) {
+        self.clear_override(edit.col, edit.row);
+        self.overrides.push(edit);
+    }
+
+    /// Puts the stone at `col`, `row` back where the seed puts it. Returns
+    /// whether it had been placed by hand.
+    pub fn clear_override(&mut self, col: u32, row: u32) -> bool {
+        let len = self.overrides.len();
+        self.overrides
+            .retain(|edit| (edit.col, edit.row) != (col, row));
+        self.overrides.len() != len
+    }
+
+    /// The shape of the stone at `col`, `row`.
+    pub fn shape_at(&self, col: u32, row: u32) -> &StoneShape {
+        static SQUARE: StoneShape = StoneShape::Square;
+        let index = match self.shape_mode {
+            ShapeMode::Global => 0,
+            ShapeMode::Rows => row as usize,
+            ShapeMode::Columns => col as usize,
+        };
+        match self.shapes.len() {
+            0 => &SQUARE,
+            len => &self.shapes[index % len],
+        }
+    }
+}
+
+/// A single stone of the grid, in grid units.
+///
+/// `x` and `y` are where the stone currently sits; the generator places it at
+/// `col` and `row`, but callers may move it, e.g. to animate it into place.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stone {
+    pub col: u32,
+    pub row: u32,
+    pub x: f32,
+    pub y: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub rotation: f32,
+}
+
+impl Stone {
+    /// The outline of `shape` displaced and rotated like this stone, in grid
+    /// units.
+    ///
+    /// The stone's cell spans `x..x + 1` and `y..y + 1`, with `y` pointing
+    /// down, so renderers only need to scale and translate these.
+    pub fn paths(&self, shape: &StoneShape) -> Vec<Path> {
+        let cx = self.x + 0.5 + self.x_offset;
+        let cy = self.y + 0.5 + self.y_offset;
+        let (sin, cos) = self.rotation.sin_cos();
+        let mut paths = shape.paths();
+        for point in paths.iter_mut().flat_map(|path| path.points.iter_mut()) {
+            let [x, y] = *point;
+            *point = [cx + x * cos - y * sin, cy + x * sin + y * cos];
+        }
+        paths
+    }
+}
+
+/// How grid units map onto an output image, in pixels.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub size: f32,
+    pub margin: f32,
+    pub line_width: f32,
+    pub background: Color,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            size: SIZE as f32,
+            margin: MARGIN as f32,
+            line_width: LINE_WIDTH,
+            background: Color::WHITESMOKE,
+        }
+    }
+}
+
+impl Layout {
+    /// The pixel size of an image showing `extent` grid units.
+    pub fn pixel_size(&self, [cols, rows]: [f32; 2]) -> [f32; 2] {
+        [
+            cols * self.size + 2.0 * self.margin,
+            rows * self.size + 2.0 * self.margin,
+        ]
+    }
+
+    /// Maps a point in grid units to pixels.
+    pub fn to_pixels(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        [self.margin + x * self.size, self.margin + y * self.size]
+    }
+}
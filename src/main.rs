@@ -1,54 +1,150 @@
-use nannou::{
-    prelude::*,
-    rand::{rngs::StdRng, Rng, SeedableRng},
-};
+use std::{io, path::PathBuf, thread};
+
+use nannou::prelude::*;
 use nannou_egui::{
     self,
     egui::{self, Align2},
+    egui_wgpu_backend::epi,
     Egui,
 };
+use nannou_schotter::{
+    animation, metadata, params, pdf, plot, print, raster, shape, svg, video, Animation, BlendMode,
+    ChaosMap, Color, ColorMode, ColorTarget, Curve, Easing, Falloff, History, Layer, Noise,
+    Occlusion, Orientation, Paint, Palette, Paper, PlotSettings, PrintSettings, Scene, Schotter,
+    SchotterParams, ShapeMode, Stagger, Stone, StoneOverride, StoneShape, Transition,
+    TransitionSettings, VideoFormat,
+};
 
-const COLS: u32 = 12;
-const LINE_WIDTH: f32 = 0.06;
-const MARGIN: u32 = 35;
-const ROWS: u32 = 22;
-const SIZE: u32 = 30;
-const WIDTH: u32 = COLS * SIZE + 2 * MARGIN;
-const HEIGHT: u32 = ROWS * SIZE + 75 + 2 * MARGIN;
+/// Room left above the grid for the control panel, in pixels.
+const PANEL_HEIGHT: f32 = 75.0;
+/// Where undo history and favorites are kept between sessions.
+const HISTORY_PATH: &str = "schotter-history.json";
+/// The longer side of the history panel's thumbnails, in pixels.
+const THUMBNAIL_SIZE: f32 = 64.0;
 
 struct Model {
     ui: Egui,
     main_window: WindowId,
-    disp_adj: f32,
-    rot_adj: f32,
-    gravel: Vec<Stone>,
-    random_seed: u64,
+    params: SchotterParams,
+    /// The layer the control panel and keys edit.
+    selected: usize,
+    /// The motion of every layer's stones.
+    gravel: Vec<Transition>,
+    /// How stones move when they fly in or the composition changes.
+    motion: TransitionSettings,
+    /// When the seed walk next changes the seed, while it walks.
+    walk_next: Option<f32>,
+    /// Seconds between the seed walk's steps.
+    walk_interval: f32,
+    walk_random: bool,
+    /// The stone being placed with the mouse, if any.
+    drag: Option<Drag>,
+    /// Whether the mouse paints chaos instead of placing stones.
+    brush: bool,
+    /// In stones.
+    brush_radius: f32,
+    /// How fast the brush changes multipliers, per second.
+    brush_strength: f32,
+    /// While the brush paints: 1 to raise multipliers, -1 to lower them.
+    painting: Option<f32>,
+    show_heatmap: bool,
+    /// The selected layer's shape list as typed in the control panel.
+    shapes_text: String,
+    palette_path: String,
+    preset_path: String,
+    plot: PlotSettings,
+    print: PrintSettings,
+    /// The pages of the next PDF export.
+    batch: pdf::Batch,
+    /// The seed gallery, shown in place of the composition while open.
+    gallery: Option<Gallery>,
+    gallery_count: u32,
+    gallery_random: bool,
+    history: History,
+    show_history: bool,
+    /// Thumbnails for the history panel, rendered as they scroll into view.
+    thumbnails: Vec<Thumbnail>,
+    animation: Animation,
+    /// The keyframes as typed in the control panel.
+    keyframes_text: String,
+    video_format: VideoFormat,
+    /// Whether MP4 export can work.
+    ffmpeg: bool,
+    /// When the animation preview started playing, if it is playing.
+    preview_since: Option<f32>,
+    /// Screenshots still being written, with the frame they were taken on and
+    /// the parameters to embed once they are on disk.
+    pending_captures: Vec<(String, u64, SchotterParams)>,
+}
+
+impl Model {
+    fn schotter(&mut self) -> &mut Schotter {
+        &mut self.params.layers[self.selected].schotter
+    }
 }
 
-struct Stone {
-    x: f32,
-    y: f32,
-    x_offset: f32,
-    y_offset: f32,
-    final_x: f32,
-    final_y: f32,
-    final_rot: f32,
+/// A history panel thumbnail and its size, or `None` if the composition could
+/// not be rendered.
+type Thumbnail = (SchotterParams, Option<(egui::TextureId, egui::Vec2)>);
+
+/// Thumbnails of the composition with other seeds for the selected layer.
+struct Gallery {
+    seeds: Vec<u64>,
+    /// The composition the thumbnails were made from, to tell when they are
+    /// out of date.
+    params: SchotterParams,
+    selected: usize,
+    scenes: Vec<Scene>,
 }
 
-impl Stone {
-    fn new(final_x: f32, final_y: f32) -> Self {
-        Stone {
-            x: 0.0,
-            y: 0.0,
-            x_offset: 0.0,
-            y_offset: 0.0,
-            final_x,
-            final_y,
-            final_rot: 0.0,
+impl Gallery {
+    fn new(params: &SchotterParams, selected: usize, seeds: Vec<u64>) -> Self {
+        let scenes = seeds
+            .iter()
+            .map(|&seed| {
+                let mut thumbnail = params.clone();
+                thumbnail.layers[selected].schotter.seed = seed;
+                Scene::new(&thumbnail)
+            })
+            .collect();
+        Gallery {
+            seeds,
+            params: params.clone(),
+            selected,
+            scenes,
         }
     }
 }
 
+/// A stone being moved, or turned with Shift held, with the mouse.
+struct Drag {
+    layer: usize,
+    /// Where the mouse went down, in the layer's grid units.
+    start: [f32; 2],
+    /// The stone as it was then.
+    stone: Stone,
+    rotate: bool,
+}
+
+/// Requests from the history panel and keys.
+enum HistoryAction {
+    Undo,
+    Redo,
+    GoTo(usize),
+    ToggleFavorite,
+    LoadFavorite(usize),
+    RemoveFavorite(usize),
+}
+
+/// Changes to the layer stack requested from the control panel.
+enum LayerAction {
+    Add,
+    Duplicate,
+    Remove,
+    Raise,
+    Lower,
+}
+
 fn main() {
     nannou::app(setup)
         .update(update)
@@ -57,140 +153,1497 @@ fn main() {
 }
 
 fn setup(app: &App) -> Model {
+    let schotter = Schotter::new(random_range(0, 1_000_000));
+    let params = SchotterParams::new(&schotter, &Default::default());
+    let (width, height) = window_size(&params);
+
     let main_window = app
         .new_window()
         .title(app.exe_name().unwrap())
-        .size(WIDTH, HEIGHT)
+        .size(width as u32, height as u32)
         .view(view)
         .raw_event(raw_ui_event)
         .key_pressed(key_pressed)
+        .mouse_pressed(mouse_pressed)
+        .mouse_moved(mouse_moved)
+        .mouse_released(mouse_released)
+        .dropped_file(dropped_file)
         .build()
         .unwrap();
 
-    let mut gravel = Vec::new();
-    for y in 0..ROWS {
-        for x in 0..COLS {
-            let stone = Stone::new(x as f32, y as f32);
-            gravel.push(stone);
-        }
-    }
-
     Model {
         main_window,
-        gravel,
+        gravel: vec![fly_in(&schotter, app.time)],
+        motion: TransitionSettings::default(),
+        walk_next: None,
+        walk_interval: 3.0,
+        walk_random: false,
+        drag: None,
+        brush: false,
+        brush_radius: 2.0,
+        brush_strength: 1.0,
+        painting: None,
+        show_heatmap: false,
         ui: Egui::from_window(&app.window(main_window).unwrap()),
-        disp_adj: 1.0,
-        rot_adj: 1.0,
-        random_seed: random_range(0, 1_000_000),
+        shapes_text: shape::format_shapes(&schotter.shapes),
+        params,
+        selected: 0,
+        palette_path: String::new(),
+        preset_path: String::from("schotter.toml"),
+        plot: PlotSettings::default(),
+        print: PrintSettings::default(),
+        batch: pdf::Batch::default(),
+        gallery: None,
+        gallery_count: 16,
+        gallery_random: false,
+        history: load_history(),
+        show_history: false,
+        thumbnails: Vec::new(),
+        keyframes_text: animation::format_keyframes(&Animation::default().keyframes),
+        animation: Animation::default(),
+        video_format: VideoFormat::default(),
+        ffmpeg: video::ffmpeg_available(),
+        preview_since: None,
+        pending_captures: Vec::new(),
+    }
+}
+
+fn window_size(params: &SchotterParams) -> (f32, f32) {
+    let [width, height] = params.pixel_size();
+    (width, height + PANEL_HEIGHT)
+}
+
+/// The intro animation, starting at `time`: every stone starts at the top
+/// left corner.
+fn fly_in(schotter: &Schotter, time: f32) -> Transition {
+    Transition::fly_in(schotter.stones(), time)
+}
+
+/// Restarts the intro animation of every layer and fits the window to the
+/// composition.
+fn reset_grid(app: &App, model: &mut Model) {
+    model.gravel = model
+        .params
+        .layers
+        .iter()
+        .map(|layer| fly_in(&layer.schotter, app.time))
+        .collect();
+    fit_window(app, model);
+}
+
+fn load_history() -> History {
+    match History::load(HISTORY_PATH) {
+        Ok(history) => history,
+        Err(params::Error::Io(err)) if err.kind() == io::ErrorKind::NotFound => History::default(),
+        Err(err) => {
+            eprintln!("Failed to load {}: {}", HISTORY_PATH, err);
+            History::default()
+        }
+    }
+}
+
+/// Saves the history and frees the thumbnails it no longer shows.
+fn history_changed(app: &App, model: &mut Model) {
+    if let Err(err) = model.history.save(HISTORY_PATH) {
+        eprintln!("Failed to save {}: {}", HISTORY_PATH, err);
+    }
+    let history = &model.history;
+    let mut unused = Vec::new();
+    model.thumbnails.retain(|(params, texture)| {
+        let shown = history.states().contains(params) || history.is_favorite(params);
+        if !shown {
+            unused.extend(texture.map(|(id, _)| id));
+        }
+        shown
+    });
+    if !unused.is_empty() {
+        model.ui.with_epi_frame(app.create_proxy(), |_, frame| {
+            for id in unused {
+                frame.tex_allocator().free(id);
+            }
+        });
+    }
+}
+
+fn apply_history_action(app: &App, model: &mut Model, action: HistoryAction) {
+    let params = match action {
+        HistoryAction::Undo => model.history.undo().cloned(),
+        HistoryAction::Redo => model.history.redo().cloned(),
+        HistoryAction::GoTo(i) => model.history.go_to(i).cloned(),
+        HistoryAction::ToggleFavorite => {
+            model.history.toggle_favorite(&model.params);
+            None
+        }
+        HistoryAction::LoadFavorite(i) => model.history.favorites.get(i).cloned(),
+        HistoryAction::RemoveFavorite(i) => {
+            model.history.favorites.remove(i);
+            None
+        }
+    };
+    if let Some(params) = params {
+        restore(app, model, params);
+    }
+    history_changed(app, model);
+}
+
+/// Shows `params` in place of the current composition. Unlike loading a
+/// preset, the stones of existing layers move to their new places; only new
+/// layers fly in.
+fn restore(app: &App, model: &mut Model, mut params: SchotterParams) {
+    if params.layers.is_empty() {
+        params.layers.push(Layer::default());
+    }
+    model.params = params;
+    let layers = &model.params.layers;
+    model.gravel.truncate(layers.len());
+    for layer in &layers[model.gravel.len()..] {
+        model.gravel.push(fly_in(&layer.schotter, app.time));
+    }
+    model.selected = model.selected.min(model.params.layers.len() - 1);
+    model.shapes_text = shape::format_shapes(&model.schotter().shapes);
+    fit_window(app, model);
+}
+
+fn toggle_walk(app: &App, model: &mut Model) {
+    model.walk_next = match model.walk_next {
+        Some(_) => None,
+        None => Some(app.time),
+    };
+}
+
+/// Steps the selected layer to the next seed, or a random one, whenever the
+/// seed walk is due.
+fn walk(app: &App, model: &mut Model) {
+    let Some(next) = model.walk_next else {
+        return;
+    };
+    if app.time < next {
+        return;
+    }
+    let random = model.walk_random;
+    let schotter = model.schotter();
+    schotter.seed = if random {
+        random_range(0, 1000000)
+    } else {
+        schotter.seed.wrapping_add(1)
+    };
+    model.walk_next = Some(app.time + model.walk_interval);
+}
+
+/// Opens the gallery on `count` seeds following `start`, or on random ones.
+fn open_gallery(model: &mut Model, start: u64) {
+    let seeds = (0..model.gallery_count as u64)
+        .map(|i| {
+            if model.gallery_random {
+                random_range(0, 1000000)
+            } else {
+                start.wrapping_add(i)
+            }
+        })
+        .collect();
+    model.gallery = Some(Gallery::new(&model.params, model.selected, seeds));
+}
+
+fn toggle_gallery(model: &mut Model) {
+    if model.gallery.take().is_none() {
+        let seed = model.schotter().seed;
+        open_gallery(model, seed);
+    }
+}
+
+/// Replaces the thumbnails with the seeds after the last one shown.
+fn next_gallery_page(model: &mut Model) {
+    let start = match &model.gallery {
+        Some(gallery) => gallery.seeds.last().map_or(0, |&seed| seed.wrapping_add(1)),
+        None => model.schotter().seed,
+    };
+    open_gallery(model, start);
+}
+
+/// Where the gallery's thumbnails go in `window`: a grid below the control
+/// panel with as many columns as makes compositions of `extent` the largest.
+fn gallery_cells(window: Rect, count: usize, [cols, rows]: [f32; 2]) -> Vec<Rect> {
+    let area = window.pad_top(PANEL_HEIGHT);
+    let grid_cols = (1..=count.max(1))
+        .max_by(|&a, &b| {
+            let scale = |grid_cols: usize| {
+                let grid_rows = count.div_ceil(grid_cols).max(1);
+                (area.w() / grid_cols as f32 / cols).min(area.h() / grid_rows as f32 / rows)
+            };
+            scale(a).total_cmp(&scale(b))
+        })
+        .unwrap_or(1);
+    let grid_rows = count.div_ceil(grid_cols).max(1);
+    let (width, height) = (area.w() / grid_cols as f32, area.h() / grid_rows as f32);
+    (0..count)
+        .map(|i| {
+            let (col, row) = ((i % grid_cols) as f32, (i / grid_cols) as f32);
+            Rect::from_x_y_w_h(
+                area.left() + (col + 0.5) * width,
+                area.top() - (row + 0.5) * height,
+                width,
+                height,
+            )
+        })
+        .collect()
+}
+
+fn fit_window(app: &App, model: &Model) {
+    if let Some(window) = app.window(model.main_window) {
+        let (width, height) = window_size(&model.params);
+        window.set_inner_size_points(width, height);
+    }
+}
+
+fn save_preset(model: &Model) {
+    if let Err(err) = model.params.save(&model.preset_path) {
+        eprintln!("Failed to save {}: {}", model.preset_path, err);
+    }
+}
+
+fn load_preset(app: &App, model: &mut Model) {
+    match SchotterParams::load(&model.preset_path) {
+        Ok(mut params) => {
+            if params.layers.is_empty() {
+                params.layers.push(Layer::default());
+            }
+            model.params = params;
+            model.selected = 0;
+            model.shapes_text = shape::format_shapes(&model.schotter().shapes);
+            reset_grid(app, model);
+        }
+        Err(err) => eprintln!("Failed to load {}: {}", model.preset_path, err),
+    }
+}
+
+fn apply_layer_action(model: &mut Model, action: LayerAction, time: f32) {
+    let layers = &mut model.params.layers;
+    let i = model.selected;
+    match action {
+        LayerAction::Add => {
+            let mut layer = Layer::new(Schotter::new(random_range(0, 1_000_000)));
+            layer.name = format!("Layer {}", layers.len() + 1);
+            model.gravel.push(fly_in(&layer.schotter, time));
+            layers.push(layer);
+            model.selected = layers.len() - 1;
+        }
+        LayerAction::Duplicate => {
+            let mut layer = layers[i].clone();
+            layer.name.push_str(" copy");
+            layers.insert(i + 1, layer);
+            model.gravel.insert(i + 1, model.gravel[i].clone());
+            model.selected = i + 1;
+        }
+        LayerAction::Remove if layers.len() > 1 => {
+            layers.remove(i);
+            model.gravel.remove(i);
+            model.selected = i.min(layers.len() - 1);
+        }
+        LayerAction::Raise if i + 1 < layers.len() => {
+            layers.swap(i, i + 1);
+            model.gravel.swap(i, i + 1);
+            model.selected = i + 1;
+        }
+        LayerAction::Lower if i > 0 => {
+            layers.swap(i, i - 1);
+            model.gravel.swap(i, i - 1);
+            model.selected = i - 1;
+        }
+        _ => {}
     }
 }
-fn update(_app: &App, model: &mut Model, _update: Update) {
+
+/// Adds the generation parameters to screenshots once they have been saved.
+fn embed_capture_params(app: &App, model: &mut Model) {
+    let frame = app.elapsed_frames();
+    if !model.pending_captures.iter().any(|(_, f, _)| *f < frame) {
+        return;
+    }
+    if let Some(window) = app.window(model.main_window) {
+        if window.await_capture_frame_jobs().is_err() {
+            return;
+        }
+    }
+    model.pending_captures.retain(|(path, f, params)| {
+        if *f >= frame {
+            return true;
+        }
+        if let Err(err) = metadata::embed_file(path, params) {
+            eprintln!("Failed to embed parameters in {}: {}", path, err);
+        }
+        false
+    });
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    let size = window_size(&model.params);
+    let selected = model.selected;
+
+    let mut save_preset_clicked = false;
+    let mut load_preset_clicked = false;
+    let mut layer_action = None;
+    let mut plot_extension = None;
+    let mut print_clicked = false;
+    let mut pdf_clicked = false;
+    let mut gallery_clicked = false;
+    let mut more_seeds_clicked = false;
+    let mut history_action = None;
+    let mut missing_thumbnails = Vec::new();
+    let mut preview_clicked = false;
+    let mut video_clicked = false;
+    let mut walk_clicked = false;
+
     // Draw control panel
     let ctx = model.ui.begin_frame();
 
+    if model.show_history {
+        egui::SidePanel::right("history").show(&ctx, |ui| {
+            history_action = history_ui(
+                ui,
+                &model.history,
+                &model.params,
+                &model.thumbnails,
+                &mut missing_thumbnails,
+            );
+        });
+    }
+
     egui::Window::new("Schotter Control Panel") // Control panel title
         .anchor(Align2::CENTER_TOP, [0.0, 1.0])
         .collapsible(true)
         .show(&ctx, |ui| {
+            // Layers
+            egui::CollapsingHeader::new("Layers").show(ui, |ui| {
+                layer_action = layers_ui(ui, &mut model.params.layers, &mut model.selected);
+            });
+            let schotter = &mut model.params.layers[model.selected].schotter;
             // Displacement slider
-            ui.add(egui::Slider::new(&mut model.disp_adj, 0.0..=5.0).text("Displacement Factor"));
+            ui.add(
+                egui::Slider::new(&mut schotter.disp_adj, 0.0..=5.0).text("Displacement Factor"),
+            );
             // Rotation slider
-            ui.add(egui::Slider::new(&mut model.rot_adj, 0.0..=5.0).text("Rotation Factor"));
+            ui.add(egui::Slider::new(&mut schotter.rot_adj, 0.0..=5.0).text("Rotation Factor"));
             // Randomizer
             ui.horizontal(|ui| {
                 if ui.add(egui::Button::new("Randomize")).clicked() {
-                    model.random_seed = random_range(0, 1000000);
-
-                    let mut gravel = Vec::new();
-                    for y in 0..ROWS {
-                        for x in 0..COLS {
-                            let stone = Stone::new(x as f32, y as f32);
-                            gravel.push(stone);
-                        }
-                    }
-                    model.gravel = gravel;
+                    schotter.seed = random_range(0, 1000000);
                 }
                 ui.add_space(20.0);
-                ui.add(egui::DragValue::new(&mut model.random_seed));
+                ui.add(egui::DragValue::new(&mut schotter.seed));
                 ui.label("Seed");
             });
+            // Gallery
+            egui::CollapsingHeader::new("Gallery").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let label = if model.gallery.is_some() {
+                        "Hide"
+                    } else {
+                        "Show"
+                    };
+                    gallery_clicked = ui.button(label).clicked();
+                    more_seeds_clicked = ui.button("More Seeds").clicked();
+                    ui.checkbox(&mut model.gallery_random, "Random");
+                });
+                ui.add(egui::Slider::new(&mut model.gallery_count, 1..=64).text("Thumbnails"));
+            });
+            // Shape
+            egui::CollapsingHeader::new("Shape").show(ui, |ui| {
+                egui::ComboBox::from_label("Mode")
+                    .selected_text(schotter.shape_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in ShapeMode::ALL {
+                            ui.selectable_value(&mut schotter.shape_mode, mode, mode.name());
+                        }
+                    });
+                ui.horizontal_wrapped(|ui| {
+                    for example in StoneShape::examples() {
+                        if ui.button(example.name()).clicked() {
+                            if schotter.shape_mode == ShapeMode::Global {
+                                schotter.shapes.clear();
+                            }
+                            schotter.shapes.push(example);
+                            model.shapes_text = shape::format_shapes(&schotter.shapes);
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Shapes");
+                    if ui.text_edit_singleline(&mut model.shapes_text).changed() {
+                        if let Ok(shapes) = shape::parse_shapes(&model.shapes_text) {
+                            schotter.shapes = shapes;
+                        }
+                    }
+                });
+            });
+            // Color
+            egui::CollapsingHeader::new("Color").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgb(&mut model.params.layout.background.0);
+                    ui.label("Background");
+                    ui.color_edit_button_srgb(&mut schotter.style.stroke.0);
+                    ui.label("Stroke");
+                    ui.color_edit_button_srgb(&mut schotter.style.fill.0);
+                    ui.label("Fill");
+                });
+                ui.add(
+                    egui::Slider::new(&mut schotter.style.fill_opacity, 0.0..=1.0)
+                        .text("Fill Opacity"),
+                );
+                let style = &mut schotter.style;
+                egui::ComboBox::from_label("Color Mode")
+                    .selected_text(style.color_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in ColorMode::ALL {
+                            ui.selectable_value(&mut style.color_mode, mode, mode.name());
+                        }
+                    });
+                egui::ComboBox::from_label("Applies To")
+                    .selected_text(style.color_target.name())
+                    .show_ui(ui, |ui| {
+                        for target in ColorTarget::ALL {
+                            ui.selectable_value(&mut style.color_target, target, target.name());
+                        }
+                    });
+                egui::ComboBox::from_label("Palette")
+                    .selected_text(style.palette.name.as_str())
+                    .show_ui(ui, |ui| {
+                        for palette in Palette::builtin() {
+                            let name = palette.name.clone();
+                            ui.selectable_value(&mut style.palette, palette, name);
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut model.palette_path)
+                        .on_hover_text(".gpl, .ase, Lospec .json or a list of hex colors");
+                    if ui.button("Import").clicked() {
+                        match Palette::load(&model.palette_path) {
+                            Ok(palette) => style.palette = palette,
+                            Err(err) => {
+                                eprintln!("Failed to import {}: {}", model.palette_path, err)
+                            }
+                        }
+                    }
+                });
+                ui.horizontal_wrapped(|ui| {
+                    let mut remove = None;
+                    for (i, color) in style.palette.colors.iter_mut().enumerate() {
+                        if ui
+                            .color_edit_button_srgb(&mut color.0)
+                            .on_hover_text("Right-click to remove")
+                            .secondary_clicked()
+                        {
+                            remove = Some(i);
+                        }
+                    }
+                    if let Some(i) = remove {
+                        style.palette.colors.remove(i);
+                    }
+                    if ui.button("+").clicked() {
+                        let last = style.palette.colors.last().copied();
+                        style.palette.colors.push(last.unwrap_or(Color::BLACK));
+                    }
+                });
+            });
+            // Noise
+            egui::CollapsingHeader::new("Noise").show(ui, |ui| {
+                egui::ComboBox::from_label("Source")
+                    .selected_text(schotter.noise.name())
+                    .show_ui(ui, |ui| {
+                        for noise in Noise::ALL {
+                            ui.selectable_value(&mut schotter.noise, noise, noise.name());
+                        }
+                    });
+                ui.add_enabled(
+                    schotter.noise.is_coherent(),
+                    egui::Slider::new(&mut schotter.noise_scale, 0.01..=1.0).text("Scale"),
+                );
+                ui.add(egui::Slider::new(&mut schotter.noise_phase, -10.0..=10.0).text("Phase"))
+                    .on_hover_text("Move every stone through the noise at once");
+                ui.add_enabled(
+                    !schotter.noise.is_coherent(),
+                    egui::Checkbox::new(&mut schotter.sequential, "Sequential (legacy)"),
+                )
+                .on_hover_text("Draw from one stream in row-major order, as older versions did");
+            });
+            // Falloff
+            egui::CollapsingHeader::new("Falloff").show(ui, |ui| {
+                falloff_ui(ui, "Displacement", &mut schotter.disp_falloff);
+                falloff_ui(ui, "Rotation", &mut schotter.rot_falloff);
+            });
+            // Motion
+            egui::CollapsingHeader::new("Motion").show(ui, |ui| {
+                motion_ui(ui, &mut model.motion);
+                ui.horizontal(|ui| {
+                    let label = if model.walk_next.is_some() {
+                        "Stop Walk"
+                    } else {
+                        "Seed Walk"
+                    };
+                    walk_clicked = ui
+                        .button(label)
+                        .on_hover_text("Keep morphing through new seeds")
+                        .clicked();
+                    ui.checkbox(&mut model.walk_random, "Random");
+                });
+                ui.add(egui::Slider::new(&mut model.walk_interval, 0.5..=30.0).text("Every (s)"));
+            });
+            // Chaos brush
+            egui::CollapsingHeader::new("Chaos Brush").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut model.brush, "Paint")
+                        .on_hover_text("Left button raises chaos, right button lowers it");
+                    ui.checkbox(&mut model.show_heatmap, "Heatmap");
+                    if ui
+                        .add_enabled(!schotter.chaos.is_empty(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        schotter.chaos.clear();
+                    }
+                });
+                ui.add(egui::Slider::new(&mut model.brush_radius, 0.5..=10.0).text("Radius"));
+                ui.add(egui::Slider::new(&mut model.brush_strength, 0.1..=5.0).text("Strength"));
+            });
+            // Grid
+            egui::CollapsingHeader::new("Grid").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} stones placed by hand",
+                        schotter.overrides.len()
+                    ))
+                    .on_hover_text(
+                        "Drag a stone to move it, Shift-drag to turn it, right-click to reset it",
+                    );
+                    if ui
+                        .add_enabled(!schotter.overrides.is_empty(), egui::Button::new("Reset"))
+                        .clicked()
+                    {
+                        schotter.overrides.clear();
+                    }
+                });
+                ui.add(egui::Slider::new(&mut schotter.cols, 1..=60).text("Columns"));
+                ui.add(egui::Slider::new(&mut schotter.rows, 1..=60).text("Rows"));
+                ui.add(
+                    egui::Slider::new(&mut model.params.layout.size, 5.0..=100.0)
+                        .text("Stone Size"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut model.params.layout.margin, 0.0..=200.0).text("Margin"),
+                );
+            });
+            // Animation
+            egui::CollapsingHeader::new("Animation").show(ui, |ui| {
+                animation_ui(ui, &mut model.animation, &mut model.keyframes_text);
+                ui.horizontal(|ui| {
+                    let label = if model.preview_since.is_some() {
+                        "Stop"
+                    } else {
+                        "Preview"
+                    };
+                    preview_clicked = ui.button(label).clicked();
+                    egui::ComboBox::from_id_source("video format")
+                        .selected_text(model.video_format.name())
+                        .show_ui(ui, |ui| {
+                            for format in VideoFormat::ALL {
+                                let enabled = format != VideoFormat::Mp4 || model.ffmpeg;
+                                ui.add_enabled_ui(enabled, |ui| {
+                                    ui.selectable_value(
+                                        &mut model.video_format,
+                                        format,
+                                        format.name(),
+                                    )
+                                })
+                                .response
+                                .on_disabled_hover_text("Needs ffmpeg on the PATH");
+                            }
+                        });
+                    video_clicked = ui.button("Export").clicked();
+                });
+            });
+            // Plotter
+            egui::CollapsingHeader::new("Plotter").show(ui, |ui| {
+                let occlusion = &mut model.params.occlusion;
+                egui::ComboBox::from_label("Hidden Lines")
+                    .selected_text(occlusion.name())
+                    .show_ui(ui, |ui| {
+                        for mode in Occlusion::ALL {
+                            ui.selectable_value(occlusion, mode, mode.name());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Clip outlines behind other stones, here and in every export");
+                plot_ui(ui, &mut model.plot);
+                ui.horizontal(|ui| {
+                    if ui.button("Export HPGL").clicked() {
+                        plot_extension = Some(".hpgl");
+                    }
+                    if ui.button("Export G-code").clicked() {
+                        plot_extension = Some(".gcode");
+                    }
+                });
+            });
+            // Print
+            egui::CollapsingHeader::new("Print").show(ui, |ui| {
+                print_ui(ui, &mut model.print, &model.params);
+                batch_ui(ui, &mut model.batch);
+                // print_ui says what is wrong with settings that cannot print.
+                let valid = model.print.validate().is_ok();
+                ui.horizontal(|ui| {
+                    print_clicked = ui
+                        .add_enabled(valid, egui::Button::new("Export PNG"))
+                        .clicked();
+                    pdf_clicked = ui
+                        .add_enabled(valid, egui::Button::new("Export PDF"))
+                        .clicked();
+                });
+            });
+            // Presets
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut model.preset_path);
+                save_preset_clicked = ui.button("Save").clicked();
+                load_preset_clicked = ui.button("Load").clicked();
+            });
         });
+    drop(ctx);
+    render_thumbnails(app, model, missing_thumbnails);
     // End control panel
 
-    let mut rng = StdRng::seed_from_u64(model.random_seed);
+    if save_preset_clicked {
+        save_preset(model);
+    }
+    if let Some(extension) = plot_extension {
+        // Ordering the paths of large grids takes a while.
+        let path = app.exe_name().unwrap() + &app.time.to_string() + extension;
+        let (params, settings) = (model.params.clone(), model.plot.clone());
+        thread::spawn(move || {
+            if let Err(err) = plot::save(&path, &params, &settings) {
+                eprintln!("Failed to export {}: {}", path, err);
+            }
+        });
+    }
+    if print_clicked {
+        // Large pages take a while, so keep the window responsive.
+        let path = app.exe_name().unwrap() + &app.time.to_string() + ".png";
+        let (params, settings) = (model.params.clone(), model.print.clone());
+        thread::spawn(move || {
+            if let Err(err) = print::save(&path, &params, &settings) {
+                eprintln!("Failed to export {}: {}", path, err);
+            }
+        });
+    }
+    if pdf_clicked {
+        let path = app.exe_name().unwrap() + &app.time.to_string() + ".pdf";
+        let pages = model.batch.pages(&model.params, model.selected);
+        let settings = model.print.clone();
+        thread::spawn(move || {
+            if let Err(err) = pdf::save(&path, &pages, &settings) {
+                eprintln!("Failed to export {}: {}", path, err);
+            }
+        });
+    }
+    if walk_clicked {
+        toggle_walk(app, model);
+    }
+    if preview_clicked {
+        model.preview_since = match model.preview_since {
+            Some(_) => None,
+            None => Some(app.time),
+        };
+    }
+    if video_clicked {
+        // Every frame is rasterized, so keep the window responsive.
+        let path = app.exe_name().unwrap() + &app.time.to_string() + model.video_format.extension();
+        let (format, params) = (model.video_format, model.params.clone());
+        let (layer, animation) = (model.selected, model.animation.clone());
+        thread::spawn(move || {
+            if let Err(err) =
+                video::save(&path, format, &params, layer, &animation, raster::BASE_DPI)
+            {
+                eprintln!("Failed to export {}: {}", path, err);
+            }
+        });
+    }
+    if let Some(action) = history_action {
+        apply_history_action(app, model, action);
+    }
+    if gallery_clicked {
+        toggle_gallery(model);
+    } else if more_seeds_clicked {
+        next_gallery_page(model);
+    }
+    if load_preset_clicked {
+        load_preset(app, model);
+    } else {
+        if let Some(action) = layer_action {
+            apply_layer_action(model, action, app.time);
+        }
+        if size != window_size(&model.params) {
+            fit_window(app, model);
+        }
+    }
+    if model.selected != selected {
+        model.shapes_text = shape::format_shapes(&model.schotter().shapes);
+    }
+    // The gallery catches up with a slider once the drag ends, rather than
+    // rebuilding every thumbnail on every frame of it.
+    let dragging = model.ui.ctx().input().pointer.any_down();
+    if let Some(gallery) = &model.gallery {
+        let changed = gallery.params != model.params || gallery.selected != model.selected;
+        if changed && !dragging {
+            let seeds = gallery.seeds.clone();
+            model.gallery = Some(Gallery::new(&model.params, model.selected, seeds));
+        }
+    }
+
+    walk(app, model);
+    paint(app, model, update.since_last.as_secs_f32());
 
-    // Set current positions for each stone
-    for stone in &mut model.gravel {
-        let factor = stone.y / ROWS as f32;
-        let disp_factor = factor * model.disp_adj;
-        let rot_factor = factor * model.rot_adj;
-        stone.x_offset = disp_factor * rng.gen_range(-0.5..0.5);
-        stone.y_offset = disp_factor * rng.gen_range(-0.5..0.5);
-        stone.final_rot = rot_factor * rng.gen_range(-PI / 4.0..PI / 4.0);
-        if stone.x < stone.final_x {
-            stone.x += 0.5 * (COLS as f32 / ROWS as f32);
+    // Slider drags are recorded once they end, not on every frame, typing
+    // once the field loses focus, and the seed walk once it stops.
+    let typing = model.ui.ctx().wants_keyboard_input();
+    let walking = model.walk_next.is_some();
+    if !dragging && !typing && !walking && model.history.record(&model.params) {
+        history_changed(app, model);
+    }
+
+    embed_capture_params(app, model);
+
+    // Stones move to wherever their layer now puts them; layers whose grid
+    // changed fly in again.
+    for (transition, layer) in model.gravel.iter_mut().zip(&model.params.layers) {
+        transition.retarget(layer.schotter.stones(), app.time, &model.motion);
+    }
+}
+
+/// Lists favorites and past states as thumbnails, newest first, and returns
+/// the one clicked, if any. Compositions without a thumbnail yet are added to
+/// `missing`.
+fn history_ui(
+    ui: &mut egui::Ui,
+    history: &History,
+    params: &SchotterParams,
+    thumbnails: &[Thumbnail],
+    missing: &mut Vec<SchotterParams>,
+) -> Option<HistoryAction> {
+    let mut action = None;
+    let row_height = THUMBNAIL_SIZE + 2.0 * ui.spacing().button_padding.y;
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(history.can_undo(), egui::Button::new("Undo"))
+            .clicked()
+        {
+            action = Some(HistoryAction::Undo);
+        }
+        if ui
+            .add_enabled(history.can_redo(), egui::Button::new("Redo"))
+            .clicked()
+        {
+            action = Some(HistoryAction::Redo);
         }
-        if stone.y < stone.final_y {
-            stone.y += 0.5;
+        let star = if history.is_favorite(params) {
+            "★ Unstar"
+        } else {
+            "☆ Star"
+        };
+        if ui.button(star).clicked() {
+            action = Some(HistoryAction::ToggleFavorite);
         }
+    });
+    ui.heading("Favorites");
+    egui::ScrollArea::vertical()
+        .id_source("favorites")
+        .max_height(ui.available_height() / 2.0)
+        .show_rows(ui, row_height, history.favorites.len(), |ui, rows| {
+            for i in rows {
+                let favorite = &history.favorites[i];
+                ui.horizontal(|ui| {
+                    if thumbnail_button(ui, thumbnails, missing, favorite).clicked() {
+                        action = Some(HistoryAction::LoadFavorite(i));
+                    }
+                    ui.label(describe(favorite));
+                    if ui.small_button("✕").on_hover_text("Unstar").clicked() {
+                        action = Some(HistoryAction::RemoveFavorite(i));
+                    }
+                });
+            }
+        });
+    ui.separator();
+    ui.heading("History");
+    let states = history.states();
+    egui::ScrollArea::vertical().id_source("history").show_rows(
+        ui,
+        row_height,
+        states.len(),
+        |ui, rows| {
+            for row in rows {
+                let i = states.len() - 1 - row;
+                ui.horizontal(|ui| {
+                    if thumbnail_button(ui, thumbnails, missing, &states[i]).clicked() {
+                        action = Some(HistoryAction::GoTo(i));
+                    }
+                    if i == history.position() {
+                        ui.strong(describe(&states[i]));
+                    } else {
+                        ui.label(describe(&states[i]));
+                    }
+                });
+            }
+        },
+    );
+    action
+}
+
+/// A button showing the thumbnail of `params`, or a blank one until it has
+/// been rendered.
+fn thumbnail_button(
+    ui: &mut egui::Ui,
+    thumbnails: &[Thumbnail],
+    missing: &mut Vec<SchotterParams>,
+    params: &SchotterParams,
+) -> egui::Response {
+    match thumbnails.iter().find(|(p, _)| p == params) {
+        Some((_, Some((texture, size)))) => ui.add(egui::ImageButton::new(*texture, *size)),
+        found => {
+            if found.is_none() && !missing.contains(params) {
+                missing.push(params.clone());
+            }
+            ui.add_sized([THUMBNAIL_SIZE, THUMBNAIL_SIZE], egui::Button::new(""))
+        }
+    }
+}
+
+/// Renders the thumbnails the history panel is missing and hands them to
+/// egui. Must be called outside the UI pass.
+fn render_thumbnails(app: &App, model: &mut Model, missing: Vec<SchotterParams>) {
+    if missing.is_empty() {
+        return;
+    }
+    let thumbnails = &mut model.thumbnails;
+    model.ui.with_epi_frame(app.create_proxy(), |_, frame| {
+        for params in missing {
+            let texture = thumbnail(frame.tex_allocator(), &params);
+            thumbnails.push((params, texture));
+        }
+    });
+}
+
+/// Renders `params` to fit [`THUMBNAIL_SIZE`] and allocates a texture for it,
+/// returning it with its size, or `None` if it cannot be rendered.
+fn thumbnail(
+    allocator: &mut dyn epi::TextureAllocator,
+    params: &SchotterParams,
+) -> Option<(egui::TextureId, egui::Vec2)> {
+    let [width, height] = params.pixel_size();
+    let dpi = raster::BASE_DPI * THUMBNAIL_SIZE / width.max(height).max(1.0);
+    let pixmap = match raster::render_at(params, dpi) {
+        Ok(pixmap) => pixmap,
+        Err(err) => {
+            eprintln!("Failed to render thumbnail: {}", err);
+            return None;
+        }
+    };
+    let pixels: Vec<egui::Color32> = pixmap
+        .data()
+        .chunks_exact(4)
+        .map(|p| egui::Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3]))
+        .collect();
+    let size = (pixmap.width() as usize, pixmap.height() as usize);
+    let texture = allocator.alloc_srgba_premultiplied(size, &pixels);
+    Some((texture, egui::vec2(size.0 as f32, size.1 as f32)))
+}
+
+/// A short caption for a composition: the first layer's seed and factors.
+fn describe(params: &SchotterParams) -> String {
+    let Some(layer) = params.layers.first() else {
+        return String::new();
+    };
+    let schotter = &layer.schotter;
+    let mut text = format!(
+        "Seed {}\nDisp {:.2}, Rot {:.2}",
+        schotter.seed, schotter.disp_adj, schotter.rot_adj
+    );
+    if params.layers.len() > 1 {
+        text.push_str(&format!("\n+{} layers", params.layers.len() - 1));
     }
+    text
+}
+
+/// Picks the selected layer and edits how it is placed, returning any change
+/// to the stack itself so the caller can keep the stones in step.
+fn layers_ui(ui: &mut egui::Ui, layers: &mut [Layer], selected: &mut usize) -> Option<LayerAction> {
+    let mut action = None;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("layer")
+            .selected_text(format!("{}: {}", *selected + 1, layers[*selected].name))
+            .show_ui(ui, |ui| {
+                for (i, layer) in layers.iter().enumerate() {
+                    ui.selectable_value(selected, i, format!("{}: {}", i + 1, layer.name));
+                }
+            });
+        if ui.button("Add").clicked() {
+            action = Some(LayerAction::Add);
+        }
+        if ui.button("Duplicate").clicked() {
+            action = Some(LayerAction::Duplicate);
+        }
+        if ui
+            .add_enabled(layers.len() > 1, egui::Button::new("Remove"))
+            .clicked()
+        {
+            action = Some(LayerAction::Remove);
+        }
+        if ui.button("Raise").clicked() {
+            action = Some(LayerAction::Raise);
+        }
+        if ui.button("Lower").clicked() {
+            action = Some(LayerAction::Lower);
+        }
+    });
+    let layer = &mut layers[*selected];
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut layer.name);
+        ui.checkbox(&mut layer.visible, "Visible");
+    });
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Blend")
+            .selected_text(layer.blend.name())
+            .show_ui(ui, |ui| {
+                for blend in BlendMode::ALL {
+                    ui.selectable_value(&mut layer.blend, blend, blend.name());
+                }
+            });
+        ui.add(egui::DragValue::new(&mut layer.offset[0]).speed(0.05));
+        ui.add(egui::DragValue::new(&mut layer.offset[1]).speed(0.05));
+        ui.label("Offset");
+    });
+    action
+}
+
+fn paper_ui(ui: &mut egui::Ui, paper: &mut Paper, orientation: &mut Orientation) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Paper")
+            .selected_text(paper.name())
+            .show_ui(ui, |ui| {
+                for standard in Paper::STANDARD {
+                    ui.selectable_value(paper, standard, standard.name());
+                }
+                let custom = Paper::Custom(paper.size());
+                ui.selectable_value(paper, custom, "Custom");
+            });
+        if let Paper::Custom([width, height]) = paper {
+            ui.add(egui::DragValue::new(width).suffix(" mm"));
+            ui.add(egui::DragValue::new(height).suffix(" mm"));
+        }
+    });
+    egui::ComboBox::from_label("Orientation")
+        .selected_text(orientation.name())
+        .show_ui(ui, |ui| {
+            for option in Orientation::ALL {
+                ui.selectable_value(orientation, option, option.name());
+            }
+        });
+}
+
+fn print_ui(ui: &mut egui::Ui, settings: &mut PrintSettings, params: &SchotterParams) {
+    paper_ui(ui, &mut settings.paper, &mut settings.orientation);
+    ui.add(egui::Slider::new(&mut settings.dpi, 72.0..=1200.0).text("DPI"));
+    ui.add(egui::Slider::new(&mut settings.margin, 0.0..=50.0).text("Margin (mm)"));
+    ui.add(egui::Slider::new(&mut settings.bleed, 0.0..=10.0).text("Bleed (mm)"));
+    ui.add(egui::Slider::new(&mut settings.line_width, 0.05..=3.0).text("Line Width (mm)"));
+    if let Err(msg) = settings.validate() {
+        ui.label(msg);
+        return;
+    }
+    let page = settings.page_layout(params);
+    let [width, height] = settings.pixel_size(&page);
+    ui.label(format!(
+        "{} x {} px, stones {:.1} mm",
+        width, height, page.stone_size
+    ));
+}
+
+/// Picks the pages of a PDF export. Batches vary the selected layer.
+fn batch_ui(ui: &mut egui::Ui, batch: &mut pdf::Batch) {
+    egui::ComboBox::from_label("PDF Pages")
+        .selected_text(batch.name())
+        .show_ui(ui, |ui| {
+            for example in pdf::Batch::examples() {
+                if ui
+                    .selectable_label(batch.name() == example.name(), example.name())
+                    .clicked()
+                    && batch.name() != example.name()
+                {
+                    *batch = example;
+                }
+            }
+        });
+    match batch {
+        pdf::Batch::Single => {}
+        pdf::Batch::Seeds { count } => {
+            ui.add(egui::Slider::new(count, 1..=100).text("Seeds"));
+        }
+        pdf::Batch::Displacement { from, to, steps } | pdf::Batch::Rotation { from, to, steps } => {
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(from).speed(0.05).prefix("from "));
+                ui.add(egui::DragValue::new(to).speed(0.05).prefix("to "));
+                ui.add(egui::Slider::new(steps, 1..=100).text("Steps"));
+            });
+        }
+    }
+}
+
+fn motion_ui(ui: &mut egui::Ui, motion: &mut TransitionSettings) {
+    easing_ui(ui, &mut motion.easing);
+    egui::ComboBox::from_label("Stagger")
+        .selected_text(motion.stagger.name())
+        .show_ui(ui, |ui| {
+            for stagger in Stagger::ALL {
+                ui.selectable_value(&mut motion.stagger, stagger, stagger.name());
+            }
+        });
+    ui.add(egui::Slider::new(&mut motion.duration, 0.0..=5.0).text("Duration (s)"));
+    ui.checkbox(&mut motion.morph, "Morph changes")
+        .on_hover_text("Tween offsets and rotations to new seeds and factors instead of jumping");
+    ui.add_enabled(
+        motion.stagger != Stagger::None,
+        egui::Slider::new(&mut motion.spread, 0.0..=0.9).text("Spread"),
+    )
+    .on_hover_text("How much of the duration stones take to set off, one after another");
+}
+
+fn easing_ui(ui: &mut egui::Ui, easing: &mut Easing) {
+    egui::ComboBox::from_label("Easing")
+        .selected_text(easing.name())
+        .show_ui(ui, |ui| {
+            for option in Easing::ALL {
+                ui.selectable_value(easing, option, option.name());
+            }
+        });
+}
+
+fn animation_ui(ui: &mut egui::Ui, animation: &mut Animation, keyframes_text: &mut String) {
+    egui::ComboBox::from_label("Animate")
+        .selected_text(animation.property.name())
+        .show_ui(ui, |ui| {
+            for property in animation::Property::ALL {
+                ui.selectable_value(&mut animation.property, property, property.name());
+            }
+        });
+    ui.horizontal(|ui| {
+        ui.label("Keyframes");
+        if ui
+            .text_edit_singleline(keyframes_text)
+            .on_hover_text("Comma separated <seconds>:<value> pairs")
+            .changed()
+        {
+            if let Ok(keyframes) = animation::parse_keyframes(keyframes_text) {
+                animation.keyframes = keyframes;
+            }
+        }
+    });
+    easing_ui(ui, &mut animation.easing);
+    ui.add(egui::Slider::new(&mut animation.duration, 0.5..=30.0).text("Duration (s)"));
+    ui.add(egui::Slider::new(&mut animation.fps, 1.0..=60.0).text("FPS"));
+    ui.checkbox(&mut animation.looping, "Loop");
+}
+
+fn plot_ui(ui: &mut egui::Ui, settings: &mut PlotSettings) {
+    paper_ui(ui, &mut settings.paper, &mut settings.orientation);
+    ui.add(egui::Slider::new(&mut settings.margin, 0.0..=50.0).text("Margin (mm)"));
+    ui.horizontal(|ui| {
+        ui.label("Pen Up");
+        ui.text_edit_singleline(&mut settings.pen_up);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pen Down");
+        ui.text_edit_singleline(&mut settings.pen_down);
+    });
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut settings.feed_rate).suffix(" mm/min"));
+        ui.label("Feed");
+        ui.add(egui::DragValue::new(&mut settings.travel_rate).suffix(" mm/min"));
+        ui.label("Travel");
+    });
+    ui.horizontal(|ui| {
+        ui.add(egui::Slider::new(&mut settings.pens, 1..=16).text("Pens"));
+        egui::ComboBox::from_label("Per")
+            .selected_text(settings.pen_by.name())
+            .show_ui(ui, |ui| {
+                for pen_by in plot::PenAssignment::ALL {
+                    ui.selectable_value(&mut settings.pen_by, pen_by, pen_by.name());
+                }
+            });
+    });
+    ui.checkbox(&mut settings.optimize, "Minimize pen travel");
+}
+
+fn falloff_ui(ui: &mut egui::Ui, label: &str, falloff: &mut Falloff) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label(label)
+            .selected_text(falloff.curve.name())
+            .show_ui(ui, |ui| {
+                for curve in Curve::ALL {
+                    ui.selectable_value(&mut falloff.curve, curve, curve.name());
+                }
+            });
+        ui.checkbox(&mut falloff.inverted, "Inverted");
+    });
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
+    let layout = &model.params.layout;
+    let [cols, rows] = model.params.extent();
     let draw = app.draw();
     let gdraw = draw
-        .scale(SIZE as f32)
+        .scale(layout.size)
         .scale_y(-1.0)
-        .x_y(COLS as f32 / -2.0 + 0.5, ROWS as f32 / -2.0 + 1.8);
+        .x_y(cols / -2.0, rows / -2.0 + PANEL_HEIGHT / 2.0 / layout.size);
 
-    draw.background().color(WHITESMOKE);
+    draw.background().color(to_srgba(layout.background, 1.0));
 
-    for stone in &model.gravel {
-        let cdraw = gdraw.x_y(stone.x, stone.y);
-        cdraw
-            .rect()
-            .no_fill()
-            .stroke(BLACK)
-            .stroke_weight(LINE_WIDTH)
-            .w_h(1.0, 1.0)
-            .x_y(stone.x_offset, stone.y_offset)
-            .rotate(stone.final_rot);
+    match &model.gallery {
+        Some(gallery) => draw_gallery(app, &draw, model, gallery),
+        None => {
+            let scene = match model.preview_since {
+                Some(since) => {
+                    let time = (app.time - since) % model.animation.duration.max(0.01);
+                    Scene::new(&model.animation.at(&model.params, model.selected, time))
+                }
+                None => {
+                    let stones: Vec<_> = model
+                        .gravel
+                        .iter()
+                        .map(|transition| transition.stones_at(app.time, &model.motion))
+                        .collect();
+                    Scene::from_stones(&model.params, &stones)
+                }
+            };
+            draw_scene(&gdraw, &scene, layout.line_width);
+            let layer = &model.params.layers[model.selected];
+            if model.show_heatmap {
+                draw_heatmap(&gdraw, &layer.schotter, layer.offset);
+            }
+            if model.brush {
+                draw.ellipse()
+                    .xy(app.mouse.position())
+                    .radius(model.brush_radius * layout.size)
+                    .no_fill()
+                    .stroke(to_srgba(layer.schotter.style.stroke, 0.5))
+                    .stroke_weight(1.0);
+            }
+        }
     }
 
     draw.to_frame(app, &frame).unwrap();
     model.ui.draw_to_frame(&frame).unwrap();
 }
 
+fn draw_scene(draw: &Draw, scene: &Scene, weight: f32) {
+    // Layers blend straight onto the frame, so unlike in the exporters, the
+    // stones of a layer also blend with each other where they overlap.
+    for group in &scene.groups {
+        let draw = draw.color_blend(blend_component(group.blend));
+        for item in &group.items {
+            for path in &item.paths {
+                draw_path(&draw, path, &item.paint, weight);
+            }
+        }
+    }
+}
+
+/// Draws the thumbnails, each with its seed underneath, and outlines the one
+/// under the mouse.
+fn draw_gallery(app: &App, draw: &Draw, model: &Model, gallery: &Gallery) {
+    const LABEL_HEIGHT: f32 = 16.0;
+    const PADDING: f32 = 8.0;
+    let [cols, rows] = model.params.extent();
+    let color = to_srgba(
+        model.params.layers[gallery.selected].schotter.style.stroke,
+        1.0,
+    );
+    let cells = gallery_cells(app.window_rect(), gallery.seeds.len(), [cols, rows]);
+    for ((cell, seed), scene) in cells.iter().zip(&gallery.seeds).zip(&gallery.scenes) {
+        let scale = ((cell.w() - 2.0 * PADDING) / cols)
+            .min((cell.h() - 2.0 * PADDING - LABEL_HEIGHT) / rows)
+            .max(0.0);
+        let tdraw = draw
+            .x_y(cell.x(), cell.y() + LABEL_HEIGHT / 2.0)
+            .scale(scale)
+            .scale_y(-1.0)
+            .x_y(cols / -2.0, rows / -2.0);
+        draw_scene(&tdraw, scene, model.params.layout.line_width);
+        draw.text(&seed.to_string())
+            .x_y(cell.x(), cell.bottom() + PADDING + LABEL_HEIGHT / 2.0)
+            .color(color);
+        if cell.contains(app.mouse.position()) {
+            draw.rect()
+                .xy(cell.xy())
+                .wh(cell.wh() - vec2(PADDING, PADDING))
+                .no_fill()
+                .stroke(color)
+                .stroke_weight(1.0);
+        }
+    }
+}
+
+/// Tints every cell of a layer by its chaos multiplier: blue where stones
+/// are calmer than the falloff alone makes them, red where they are wilder.
+fn draw_heatmap(draw: &Draw, schotter: &Schotter, [dx, dy]: [f32; 2]) {
+    for row in 0..schotter.rows {
+        for col in 0..schotter.cols {
+            let value = schotter.chaos.get(col, row);
+            let color = if value < 1.0 {
+                srgba(0.0, 0.3, 1.0, (1.0 - value) * 0.5)
+            } else {
+                srgba(1.0, 0.1, 0.0, (value - 1.0) / (ChaosMap::MAX - 1.0) * 0.5)
+            };
+            draw.rect()
+                .x_y(col as f32 + 0.5 + dx, row as f32 + 0.5 + dy)
+                .w_h(1.0, 1.0)
+                .color(color);
+        }
+    }
+}
+
+fn draw_path(draw: &Draw, path: &shape::Path, paint: &Paint, weight: f32) {
+    let points = path.points.iter().map(|&[x, y]| pt2(x, y));
+    let stroke = to_srgba(paint.stroke, 1.0);
+    if !path.closed {
+        draw.polyline().weight(weight).color(stroke).points(points);
+        return;
+    }
+    let polygon = draw.polygon().stroke(stroke).stroke_weight(weight);
+    match paint.fill {
+        Some((fill, opacity)) => polygon.color(to_srgba(fill, opacity)).points(points),
+        None => polygon.no_fill().points(points),
+    };
+}
+
+fn blend_component(blend: BlendMode) -> wgpu::BlendComponent {
+    use wgpu::{BlendFactor, BlendOperation};
+    let (src_factor, dst_factor, operation) = match blend {
+        BlendMode::Normal => (
+            BlendFactor::SrcAlpha,
+            BlendFactor::OneMinusSrcAlpha,
+            BlendOperation::Add,
+        ),
+        BlendMode::Multiply => (
+            BlendFactor::Dst,
+            BlendFactor::OneMinusSrcAlpha,
+            BlendOperation::Add,
+        ),
+        BlendMode::Screen => (
+            BlendFactor::One,
+            BlendFactor::OneMinusSrc,
+            BlendOperation::Add,
+        ),
+        BlendMode::Add => (BlendFactor::SrcAlpha, BlendFactor::One, BlendOperation::Add),
+        BlendMode::Darken => (BlendFactor::One, BlendFactor::One, BlendOperation::Min),
+        BlendMode::Lighten => (BlendFactor::One, BlendFactor::One, BlendOperation::Max),
+    };
+    wgpu::BlendComponent {
+        src_factor,
+        dst_factor,
+        operation,
+    }
+}
+
+fn to_srgba(Color([r, g, b]): Color, alpha: f32) -> Srgba {
+    srgba(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, alpha)
+}
+
 fn raw_ui_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
     model.ui.handle_raw_event(event);
 }
 
+/// Converts a point in the window to the grid units of the layer at index
+/// `layer`, undoing the transform in `view`.
+fn layer_point(model: &Model, layer: usize, position: Point2) -> [f32; 2] {
+    let size = model.params.layout.size;
+    let [cols, rows] = model.params.extent();
+    let [dx, dy] = model.params.layers[layer].offset;
+    [
+        position.x / size + cols / 2.0 - dx,
+        rows / 2.0 - (position.y + PANEL_HEIGHT / 2.0) / size - dy,
+    ]
+}
+
+/// The stone whose center is nearest to `point`, if the point is on it.
+fn stone_at(schotter: &Schotter, [x, y]: [f32; 2]) -> Option<Stone> {
+    schotter
+        .stones()
+        .into_iter()
+        .map(|stone| {
+            let dx = stone.x + 0.5 + stone.x_offset - x;
+            let dy = stone.y + 0.5 + stone.y_offset - y;
+            (stone, dx * dx + dy * dy)
+        })
+        .filter(|&(_, distance)| distance < 0.5)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(stone, _)| stone)
+}
+
+/// Clicking a thumbnail in the gallery loads its seed and closes the gallery.
+/// Otherwise the mouse edits the stones of the selected layer.
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if model.ui.ctx().wants_pointer_input() || model.preview_since.is_some() {
+        return;
+    }
+    let Some(gallery) = &model.gallery else {
+        if model.brush {
+            model.painting = match button {
+                MouseButton::Left => Some(1.0),
+                MouseButton::Right => Some(-1.0),
+                _ => None,
+            };
+        } else {
+            pick_stone(app, model, button);
+        }
+        return;
+    };
+    if button != MouseButton::Left {
+        return;
+    }
+    let cells = gallery_cells(
+        app.window_rect(),
+        gallery.seeds.len(),
+        model.params.extent(),
+    );
+    let position = app.mouse.position();
+    let Some(i) = cells.iter().position(|cell| cell.contains(position)) else {
+        return;
+    };
+    let (seed, selected) = (gallery.seeds[i], gallery.selected);
+    model.gallery = None;
+    model.selected = selected;
+    model.schotter().seed = seed;
+}
+
+/// The left button picks up the stone under the mouse, and the right button
+/// puts it back where the seed placed it.
+fn pick_stone(app: &App, model: &mut Model, button: MouseButton) {
+    let layer = model.selected;
+    let point = layer_point(model, layer, app.mouse.position());
+    let Some(stone) = stone_at(&model.params.layers[layer].schotter, point) else {
+        return;
+    };
+    match button {
+        MouseButton::Left => {
+            model.drag = Some(Drag {
+                layer,
+                start: point,
+                stone,
+                rotate: app.keys.mods.shift(),
+            });
+        }
+        MouseButton::Right => {
+            model.schotter().clear_override(stone.col, stone.row);
+        }
+        _ => {}
+    }
+}
+
+/// Moves the stone being dragged, or turns it about its center.
+fn mouse_moved(_app: &App, model: &mut Model, position: Point2) {
+    let Some(drag) = &model.drag else {
+        return;
+    };
+    let [x, y] = layer_point(model, drag.layer, position);
+    let stone = drag.stone;
+    let edit = if drag.rotate {
+        let center = [
+            stone.x + 0.5 + stone.x_offset,
+            stone.y + 0.5 + stone.y_offset,
+        ];
+        let angle = |[px, py]: [f32; 2]| (py - center[1]).atan2(px - center[0]);
+        StoneOverride {
+            rotation: stone.rotation + angle([x, y]) - angle(drag.start),
+            ..stone.into()
+        }
+    } else {
+        StoneOverride {
+            x_offset: stone.x_offset + x - drag.start[0],
+            y_offset: stone.y_offset + y - drag.start[1],
+            ..stone.into()
+        }
+    };
+    let layer = drag.layer;
+    let schotter = &mut model.params.layers[layer].schotter;
+    schotter.set_override(edit);
+    // The stone follows the mouse instead of easing after it.
+    model.gravel[layer] = Transition::still(schotter.stones());
+}
+
+fn mouse_released(_app: &App, model: &mut Model, _button: MouseButton) {
+    model.drag = None;
+    model.painting = None;
+}
+
+/// Paints chaos under the mouse onto the selected layer for as long as a
+/// button is held, `dt` seconds' worth.
+fn paint(app: &App, model: &mut Model, dt: f32) {
+    let Some(sign) = model.painting else {
+        return;
+    };
+    let layer = model.selected;
+    let center = layer_point(model, layer, app.mouse.position());
+    let strength = sign * model.brush_strength * dt;
+    let schotter = &mut model.params.layers[layer].schotter;
+    let (cols, rows) = (schotter.cols, schotter.rows);
+    schotter
+        .chaos
+        .paint(cols, rows, center, model.brush_radius, strength);
+    model.gravel[layer] = Transition::still(schotter.stones());
+}
+
+/// Dropping a preset or an exported PNG onto the window loads it.
+fn dropped_file(app: &App, model: &mut Model, path: PathBuf) {
+    model.preset_path = path.to_string_lossy().into_owned();
+    load_preset(app, model);
+}
+
 fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    // Keys typed into a text field are not shortcuts.
+    if model.ui.ctx().wants_keyboard_input() {
+        return;
+    }
     match key {
         Key::R => {
-            model.random_seed = random_range(0, 1000000);
+            model.schotter().seed = random_range(0, 1000000);
         }
-        Key::S => match app.window(model.main_window) {
-            Some(window) => {
-                window.capture_frame(app.exe_name().unwrap() + &app.time.to_string() + ".png");
+        Key::S => {
+            if let Some(window) = app.window(model.main_window) {
+                let path = app.exe_name().unwrap() + &app.time.to_string() + ".png";
+                window.capture_frame(&path);
+                model
+                    .pending_captures
+                    .push((path, app.elapsed_frames(), model.params.clone()));
             }
-            None => {}
-        },
-        Key::Up => {
-            model.disp_adj += 0.1;
         }
-        Key::Down => {
-            if model.disp_adj > 0.0 {
-                model.disp_adj -= 0.1;
+        Key::V => {
+            let path = app.exe_name().unwrap() + &app.time.to_string() + ".svg";
+            if let Err(err) = svg::save(&path, &model.params) {
+                eprintln!("Failed to export {}: {}", path, err);
             }
         }
+        Key::G => toggle_gallery(model),
+        Key::W => toggle_walk(app, model),
+        Key::H => model.show_history = !model.show_history,
+        Key::Z => apply_history_action(app, model, HistoryAction::Undo),
+        Key::Y => apply_history_action(app, model, HistoryAction::Redo),
+        Key::F => apply_history_action(app, model, HistoryAction::ToggleFavorite),
+        Key::P => save_preset(model),
+        Key::L => load_preset(app, model),
+        Key::Up => {
+            model.schotter().disp_adj += 0.1;
+        }
+        Key::Down if model.schotter().disp_adj > 0.0 => {
+            model.schotter().disp_adj -= 0.1;
+        }
         Key::Right => {
-            model.rot_adj += 0.1;
+            model.schotter().rot_adj += 0.1;
         }
-        Key::Left => {
-            if model.rot_adj > 0.0 {
-                model.rot_adj -= 0.1;
-            }
+        Key::Left if model.schotter().rot_adj > 0.0 => {
+            model.schotter().rot_adj -= 0.1;
         }
         _other_key => {}
     }
@@ -0,0 +1,252 @@
+//! Generation parameters embedded in PNG text chunks.
+//!
+//! Every PNG written by this crate carries a `Software` chunk with the crate
+//! version and a `Schotter` chunk holding the preset as TOML, so the image can
+//! be turned back into the composition that produced it. The preset may hold
+//! any UTF-8, such as layer names, so it goes in an `iTXt` chunk; older
+//! versions wrote it to a `tEXt` chunk, which still loads.
+
+use std::{borrow::Cow, fs, path::Path};
+
+use crate::params::{Error, SchotterParams};
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const KEYWORD: &str = "Schotter";
+const SOFTWARE: &str = "Software";
+
+/// Returns a copy of `png` with `params` stored in its text chunks, replacing
+/// any parameters that were embedded before.
+pub fn embed(png: &[u8], params: &SchotterParams) -> Result<Vec<u8>, Error> {
+    let chunks = chunks(png)?;
+    let software = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let toml = params.to_toml()?;
+
+    let mut out = SIGNATURE.to_vec();
+    for (i, (kind, data)) in chunks.into_iter().enumerate() {
+        if matches!(text(kind, data), Some((KEYWORD | SOFTWARE, _))) {
+            continue;
+        }
+        write_chunk(&mut out, kind, data);
+        // IHDR must come first, so the text goes right after it.
+        if i == 0 {
+            write_chunk(&mut out, *b"tEXt", &text_data(SOFTWARE, &software));
+            write_chunk(&mut out, *b"iTXt", &international_text_data(KEYWORD, &toml));
+        }
+    }
+    Ok(out)
+}
+
+/// Returns a copy of `png` with a `pHYs` chunk giving its resolution, so
+/// image editors and printers pick up the intended physical size.
+pub fn set_dpi(png: &[u8], dpi: f32) -> Result<Vec<u8>, Error> {
+    let pixels_per_meter = ((dpi / 0.0254).round() as u32).to_be_bytes();
+    let mut phys = [0; 9];
+    phys[..4].copy_from_slice(&pixels_per_meter);
+    phys[4..8].copy_from_slice(&pixels_per_meter);
+    phys[8] = 1; // the unit is the meter
+
+    let mut out = SIGNATURE.to_vec();
+    for (i, (kind, data)) in chunks(png)?.into_iter().enumerate() {
+        if kind == *b"pHYs" {
+            continue;
+        }
+        write_chunk(&mut out, kind, data);
+        if i == 0 {
+            write_chunk(&mut out, *b"pHYs", &phys);
+        }
+    }
+    Ok(out)
+}
+
+/// Reads back the parameters stored by [`embed`].
+pub fn extract(png: &[u8]) -> Result<SchotterParams, Error> {
+    for (kind, data) in chunks(png)? {
+        if let Some((KEYWORD, toml)) = text(kind, data) {
+            return SchotterParams::from_toml(&toml);
+        }
+    }
+    Err(Error::MissingParams)
+}
+
+/// Embeds `params` into the PNG file at `path`, rewriting it in place.
+pub fn embed_file<P: AsRef<Path>>(path: P, params: &SchotterParams) -> Result<(), Error> {
+    let png = embed(&fs::read(&path)?, params)?;
+    Ok(fs::write(path, png)?)
+}
+
+pub fn extract_file<P: AsRef<Path>>(path: P) -> Result<SchotterParams, Error> {
+    extract(&fs::read(path)?)
+}
+
+/// A chunk's type and data.
+type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits a PNG into its chunks.
+fn chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    let mut rest = png.strip_prefix(&SIGNATURE[..]).ok_or(Error::NotPng)?;
+    let mut chunks = Vec::new();
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Err(Error::NotPng);
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() < 12 + len {
+            return Err(Error::NotPng);
+        }
+        let kind = [rest[4], rest[5], rest[6], rest[7]];
+        chunks.push((kind, &rest[8..8 + len]));
+        rest = &rest[12 + len..];
+    }
+    Ok(chunks)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&kind);
+    hasher.update(data);
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// Splits a `tEXt` or uncompressed `iTXt` chunk into keyword and text, or
+/// returns `None` for any other chunk.
+fn text(kind: [u8; 4], data: &[u8]) -> Option<(&str, Cow<'_, str>)> {
+    let nul = data.iter().position(|&b| b == 0)?;
+    let keyword = std::str::from_utf8(&data[..nul]).ok()?;
+    let rest = &data[nul + 1..];
+    match &kind {
+        // Latin-1, but older versions of this crate wrote UTF-8 here.
+        b"tEXt" => match std::str::from_utf8(rest) {
+            Ok(text) => Some((keyword, Cow::Borrowed(text))),
+            Err(_) => Some((keyword, rest.iter().map(|&b| b as char).collect())),
+        },
+        // A compression flag and method, both zero when uncompressed, then
+        // the language tag and translated keyword, each ending in a NUL, then
+        // UTF-8 text.
+        b"iTXt" => {
+            let mut fields = rest.strip_prefix(&[0, 0][..])?.splitn(3, |&b| b == 0);
+            let text = std::str::from_utf8(fields.nth(2)?).ok()?;
+            Some((keyword, Cow::Borrowed(text)))
+        }
+        _ => None,
+    }
+}
+
+fn text_data(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(keyword.len() + 1 + text.len());
+    data.extend_from_slice(keyword.as_bytes());
+    data.push(0);
+    data.extend_from_slice(text.as_bytes());
+    data
+}
+
+/// An uncompressed `iTXt` chunk with no language tag.
+fn international_text_data(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(keyword.len() + 5 + text.len());
+    data.extend_from_slice(keyword.as_bytes());
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.extend_from_slice(text.as_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layer, Schotter};
+
+    /// A PNG's chunk layout, without a decodable image.
+    fn png() -> Vec<u8> {
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, *b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        write_chunk(&mut png, *b"IDAT", &[1, 2, 3]);
+        write_chunk(&mut png, *b"IEND", &[]);
+        png
+    }
+
+    fn params() -> SchotterParams {
+        let mut layer = Layer::new(Schotter::new(7));
+        layer.name = String::from("Kieselsteine über Wasser");
+        let mut params = SchotterParams::default();
+        params.layers.push(layer);
+        params
+    }
+
+    /// The chunk types, checking every CRC on the way.
+    fn kinds(png: &[u8]) -> Vec<[u8; 4]> {
+        let mut rest = &png[SIGNATURE.len()..];
+        let mut kinds = Vec::new();
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc32fast::hash(&rest[4..8 + len]), crc);
+            kinds.push(rest[4..8].try_into().unwrap());
+            rest = &rest[12 + len..];
+        }
+        kinds
+    }
+
+    #[test]
+    fn embedded_params_round_trip() {
+        let params = params();
+        let png = embed(&png(), &params).unwrap();
+        assert_eq!(extract(&png).unwrap(), params);
+        assert_eq!(
+            kinds(&png),
+            [b"IHDR", b"tEXt", b"iTXt", b"IDAT", b"IEND"].map(|kind| *kind)
+        );
+    }
+
+    #[test]
+    fn embedding_again_replaces_the_params() {
+        let png = embed(&png(), &SchotterParams::default()).unwrap();
+        let png = embed(&png, &params()).unwrap();
+        assert_eq!(extract(&png).unwrap(), params());
+        assert_eq!(
+            kinds(&png),
+            [b"IHDR", b"tEXt", b"iTXt", b"IDAT", b"IEND"].map(|kind| *kind)
+        );
+    }
+
+    #[test]
+    fn params_in_text_chunks_still_load() {
+        let params = params();
+        let toml = params.to_toml().unwrap();
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, *b"IHDR", &[0; 13]);
+        write_chunk(&mut png, *b"tEXt", &text_data(KEYWORD, &toml));
+        write_chunk(&mut png, *b"IEND", &[]);
+        assert_eq!(extract(&png).unwrap(), params);
+
+        let png = embed(&png, &params).unwrap();
+        assert_eq!(
+            kinds(&png),
+            [b"IHDR", b"tEXt", b"iTXt", b"IEND"].map(|kind| *kind)
+        );
+    }
+
+    #[test]
+    fn missing_params_and_bad_files_are_errors() {
+        assert!(matches!(extract(&png()), Err(Error::MissingParams)));
+        assert!(matches!(extract(b"GIF89a"), Err(Error::NotPng)));
+        let mut truncated = png();
+        truncated.pop();
+        assert!(matches!(extract(&truncated), Err(Error::NotPng)));
+    }
+
+    #[test]
+    fn dpi_is_stored_in_pixels_per_meter() {
+        let png = set_dpi(&png(), 300.0).unwrap();
+        let png = set_dpi(&png, 300.0).unwrap();
+        assert_eq!(
+            kinds(&png),
+            [b"IHDR", b"pHYs", b"IDAT", b"IEND"].map(|kind| *kind)
+        );
+        let (_, phys) = chunks(&png).unwrap()[1];
+        // 300 / 0.0254 = 11811.02
+        let ppm = 11811_u32.to_be_bytes();
+        assert_eq!(phys, [&ppm[..], &ppm[..], &[1]].concat());
+    }
+}
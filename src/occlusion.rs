@@ -0,0 +1,331 @@
+//! Hidden-line removal: treating stones as opaque so that overlapping
+//! outlines are clipped instead of drawn on top of each other.
+//!
+//! Plotters draw every line they are given, so heavily displaced grids
+//! double up ink where stones overlap. Clipping leaves clean polylines that
+//! never cross the inside of another stone.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Path, Scene};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Occlusion {
+    /// Every outline is drawn in full.
+    #[default]
+    None,
+    /// Stones drawn earlier hide the ones drawn after them.
+    EarlierOnTop,
+    /// Stones drawn later hide the ones drawn before them, as paint would.
+    LaterOnTop,
+}
+
+impl Occlusion {
+    pub const ALL: [Occlusion; 3] = [
+        Occlusion::None,
+        Occlusion::EarlierOnTop,
+        Occlusion::LaterOnTop,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Occlusion::None => "none",
+            Occlusion::EarlierOnTop => "earlier-on-top",
+            Occlusion::LaterOnTop => "later-on-top",
+        }
+    }
+}
+
+impl FromStr for Occlusion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Occlusion::ALL
+            .into_iter()
+            .find(|occlusion| occlusion.name() == s)
+            .ok_or_else(|| format!("unknown occlusion: {}", s))
+    }
+}
+
+/// An axis-aligned bounding box: min x, min y, max x, max y.
+type Bounds = [f32; 4];
+
+/// Clips the outlines of every item in `scene` against the closed outlines of
+/// the items in front of it, across all layers in drawing order.
+///
+/// Outlines that end up partly hidden become open paths, so they lose their
+/// fill; fully visible ones are kept as they are.
+pub fn clip(scene: &mut Scene, occlusion: Occlusion) {
+    if occlusion == Occlusion::None {
+        return;
+    }
+
+    // The solid outlines of every item, taken before anything is clipped.
+    let solids: Vec<(Bounds, Vec<Vec<[f32; 2]>>)> = scene
+        .items()
+        .map(|item| {
+            let polygons: Vec<_> = item
+                .paths
+                .iter()
+                .filter(|path| path.closed && path.points.len() >= 3)
+                .map(|path| path.points.clone())
+                .collect();
+            (bounds(polygons.iter().flatten()), polygons)
+        })
+        .collect();
+
+    let items = scene.groups.iter_mut().flat_map(|group| &mut group.items);
+    for (i, item) in items.enumerate() {
+        let in_front = match occlusion {
+            Occlusion::EarlierOnTop => &solids[..i],
+            _ => &solids[i + 1..],
+        };
+        let item_bounds = bounds(item.paths.iter().flat_map(|path| &path.points));
+        let occluders: Vec<&Vec<[f32; 2]>> = in_front
+            .iter()
+            .filter(|(b, _)| overlaps(*b, item_bounds))
+            .flat_map(|(_, polygons)| polygons)
+            .collect();
+        if occluders.is_empty() {
+            continue;
+        }
+        item.paths = item
+            .paths
+            .iter()
+            .flat_map(|path| clip_path(path, &occluders))
+            .collect();
+    }
+}
+
+/// The visible pieces of `path` outside all of `occluders`.
+fn clip_path(path: &Path, occluders: &[&Vec<[f32; 2]>]) -> Vec<Path> {
+    let points = &path.points;
+    if points.len() < 2 {
+        return vec![path.clone()];
+    }
+    let segments = if path.closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+
+    let mut pieces: Vec<Vec<[f32; 2]>> = Vec::new();
+    let mut current: Vec<[f32; 2]> = Vec::new();
+    let mut hidden_any = false;
+    for (k, &a) in points.iter().enumerate().take(segments) {
+        let b = points[(k + 1) % points.len()];
+        let mut ts = vec![0.0, 1.0];
+        for polygon in occluders {
+            for (e, &c) in polygon.iter().enumerate() {
+                let d = polygon[(e + 1) % polygon.len()];
+                if let Some(t) = intersection(a, b, c, d) {
+                    ts.push(t);
+                }
+            }
+        }
+        ts.sort_by(|x, y| x.total_cmp(y));
+        ts.dedup_by(|x, y| (*x - *y).abs() < 1e-6);
+
+        for pair in ts.windows(2) {
+            let (t0, t1) = (pair[0], pair[1]);
+            let mid = lerp(a, b, (t0 + t1) / 2.0);
+            if occluders.iter().any(|polygon| contains(polygon, mid)) {
+                hidden_any = true;
+                if current.len() >= 2 {
+                    pieces.push(std::mem::take(&mut current));
+                } else {
+                    current.clear();
+                }
+            } else {
+                if current.is_empty() {
+                    current.push(lerp(a, b, t0));
+                }
+                current.push(lerp(a, b, t1));
+            }
+        }
+    }
+    if !hidden_any {
+        return vec![path.clone()];
+    }
+    if current.len() >= 2 {
+        // A closed outline that is visible where it starts and ends continues
+        // through its first point, so join the last piece onto the first.
+        match pieces.first_mut() {
+            Some(first) if path.closed && same_point(first[0], *current.last().unwrap()) => {
+                current.extend_from_slice(&first[1..]);
+                *first = current;
+            }
+            _ => pieces.push(current),
+        }
+    }
+    pieces.into_iter().map(Path::open).collect()
+}
+
+/// Where segment `ab` crosses segment `cd`, as a fraction of the way from `a`
+/// to `b`. Parallel segments never cross.
+fn intersection(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2]) -> Option<f32> {
+    let r = [b[0] - a[0], b[1] - a[1]];
+    let s = [d[0] - c[0], d[1] - c[1]];
+    let denom = cross(r, s);
+    if denom.abs() < 1e-9 {
+        return None;
+    }
+    let ac = [c[0] - a[0], c[1] - a[1]];
+    let t = cross(ac, s) / denom;
+    let u = cross(ac, r) / denom;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Whether `point` is inside `polygon`, by the even-odd rule.
+fn contains(polygon: &[[f32; 2]], [x, y]: [f32; 2]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for (i, &[xi, yi]) in polygon.iter().enumerate() {
+        let [xj, yj] = polygon[j];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn same_point([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> bool {
+    (ax - bx).abs() < 1e-5 && (ay - by).abs() < 1e-5
+}
+
+fn cross([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    ax * by - ay * bx
+}
+
+fn lerp([ax, ay]: [f32; 2], [bx, by]: [f32; 2], t: f32) -> [f32; 2] {
+    [ax + (bx - ax) * t, ay + (by - ay) * t]
+}
+
+fn bounds<'a>(points: impl Iterator<Item = &'a [f32; 2]>) -> Bounds {
+    points.fold(
+        [
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        ],
+        |[x0, y0, x1, y1], &[x, y]| [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+    )
+}
+
+fn overlaps(a: Bounds, b: Bounds) -> bool {
+    a[0] <= b[2] && b[0] <= a[2] && a[1] <= b[3] && b[1] <= a[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scene::{Group, Item},
+        BlendMode, Color, Paint,
+    };
+
+    fn square(x: f32, y: f32, size: f32) -> Item {
+        Item {
+            col: 0,
+            row: 0,
+            paths: vec![Path::closed(vec![
+                [x, y],
+                [x + size, y],
+                [x + size, y + size],
+                [x, y + size],
+            ])],
+            paint: Paint {
+                stroke: Color::BLACK,
+                fill: None,
+            },
+        }
+    }
+
+    fn scene(items: Vec<Item>) -> Scene {
+        Scene {
+            groups: vec![Group {
+                layer: 0,
+                name: String::new(),
+                blend: BlendMode::Normal,
+                items,
+            }],
+        }
+    }
+
+    fn length(paths: &[Path]) -> f32 {
+        paths
+            .iter()
+            .flat_map(|path| path.points.windows(2))
+            .map(|pair| (pair[1][0] - pair[0][0]).hypot(pair[1][1] - pair[0][1]))
+            .sum()
+    }
+
+    #[test]
+    fn apart_stones_are_untouched() {
+        let original = scene(vec![square(0.0, 0.0, 1.0), square(2.0, 0.0, 1.0)]);
+        for occlusion in Occlusion::ALL {
+            let mut clipped = original.clone();
+            clip(&mut clipped, occlusion);
+            assert_eq!(clipped, original, "{}", occlusion.name());
+        }
+    }
+
+    #[test]
+    fn none_leaves_overlaps() {
+        let original = scene(vec![square(0.0, 0.0, 1.0), square(0.5, 0.5, 1.0)]);
+        let mut clipped = original.clone();
+        clip(&mut clipped, Occlusion::None);
+        assert_eq!(clipped, original);
+    }
+
+    #[test]
+    fn earlier_stones_hide_later_ones() {
+        let mut clipped = scene(vec![square(0.0, 0.0, 1.0), square(0.5, 0.5, 1.0)]);
+        clip(&mut clipped, Occlusion::EarlierOnTop);
+        let items = &clipped.groups[0].items;
+        assert_eq!(items[0], square(0.0, 0.0, 1.0));
+        // The two half edges inside the first square are gone, and the rest
+        // of the outline is one open path around the outside.
+        let paths = &items[1].paths;
+        assert_eq!(paths.len(), 1);
+        assert!(!paths[0].closed);
+        assert!((length(paths) - 3.0).abs() < 1e-4);
+        for &[x, y] in &paths[0].points {
+            assert!(!(x > 0.0 && x < 1.0 && y > 0.0 && y < 1.0), "{:?}", [x, y]);
+        }
+    }
+
+    #[test]
+    fn later_stones_hide_earlier_ones() {
+        let mut clipped = scene(vec![square(0.0, 0.0, 1.0), square(0.5, 0.5, 1.0)]);
+        clip(&mut clipped, Occlusion::LaterOnTop);
+        let items = &clipped.groups[0].items;
+        assert_eq!(items[1], square(0.5, 0.5, 1.0));
+        assert!(items[0].paths.iter().all(|path| !path.closed));
+        assert!((length(&items[0].paths) - 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn covered_stones_vanish() {
+        let mut clipped = scene(vec![square(0.0, 0.0, 2.0), square(0.5, 0.5, 1.0)]);
+        clip(&mut clipped, Occlusion::EarlierOnTop);
+        assert!(clipped.groups[0].items[1].paths.is_empty());
+    }
+
+    #[test]
+    fn occlusion_crosses_layers() {
+        let mut clipped = scene(vec![square(0.0, 0.0, 1.0)]);
+        let mut top = clipped.groups[0].clone();
+        top.layer = 1;
+        top.items = vec![square(0.5, 0.5, 1.0)];
+        clipped.groups.push(top);
+        clip(&mut clipped, Occlusion::LaterOnTop);
+        assert!((length(&clipped.groups[0].items[0].paths) - 3.0).abs() < 1e-4);
+        assert_eq!(clipped.groups[1].items[0], square(0.5, 0.5, 1.0));
+    }
+}
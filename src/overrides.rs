@@ -0,0 +1,38 @@
+//! Stones placed by hand, replacing the offsets and rotation that the seed
+//! gives them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Stone;
+
+/// The offsets and rotation of the stone at `col`, `row`, whatever the seed
+/// and factors say.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoneOverride {
+    pub col: u32,
+    pub row: u32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub rotation: f32,
+}
+
+impl StoneOverride {
+    pub fn apply(&self, stone: &mut Stone) {
+        stone.x_offset = self.x_offset;
+        stone.y_offset = self.y_offset;
+        stone.rotation = self.rotation;
+    }
+}
+
+/// Keeps `stone` as it is.
+impl From<Stone> for StoneOverride {
+    fn from(stone: Stone) -> Self {
+        StoneOverride {
+            col: stone.col,
+            row: stone.row,
+            x_offset: stone.x_offset,
+            y_offset: stone.y_offset,
+            rotation: stone.rotation,
+        }
+    }
+}
@@ -0,0 +1,431 @@
+//! Color palettes: built-in ones, and importers for common palette formats.
+
+use std::{error, fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Color;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PaletteRepr")]
+pub struct Palette {
+    pub name: String,
+    pub colors: Vec<Color>,
+}
+
+/// Presets may store a palette as a bare list of colors.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaletteRepr {
+    Colors(Vec<Color>),
+    Named {
+        #[serde(default)]
+        name: String,
+        colors: Vec<Color>,
+    },
+}
+
+impl From<PaletteRepr> for Palette {
+    fn from(repr: PaletteRepr) -> Self {
+        match repr {
+            PaletteRepr::Colors(colors) => Palette::new("Custom", colors),
+            PaletteRepr::Named { name, colors } => Palette::new(name, colors),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::builtin().swap_remove(1)
+    }
+}
+
+impl Palette {
+    pub fn new(name: impl Into<String>, colors: Vec<Color>) -> Self {
+        Palette {
+            name: name.into(),
+            colors,
+        }
+    }
+
+    pub fn builtin() -> Vec<Palette> {
+        let palette = |name: &str, colors: &[u32]| {
+            let colors = colors
+                .iter()
+                .map(|rgb| Color([(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8]))
+                .collect();
+            Palette::new(name, colors)
+        };
+        vec![
+            palette("Ink", &[0x000000]),
+            palette("Nees", &[0x1d3557, 0xe63946]),
+            palette("Bauhaus", &[0xbe1e2d, 0xffde17, 0x21409a, 0x231f20]),
+            palette(
+                "Sunset",
+                &[0x355070, 0x6d597a, 0xb56576, 0xe56b6f, 0xeaac8b],
+            ),
+            palette("Ocean", &[0x03045e, 0x0077b6, 0x00b4d8, 0x90e0ef]),
+            palette("Earth", &[0x606c38, 0x283618, 0xdda15e, 0xbc6c25]),
+            palette(
+                "Pastel",
+                &[0xcdb4db, 0xffc8dd, 0xffafcc, 0xbde0fe, 0xa2d2ff],
+            ),
+        ]
+    }
+
+    /// Imports a palette, picking the format from the file extension: `.gpl`
+    /// for GIMP, `.ase` for Adobe, `.json` for Lospec and anything else for a
+    /// plain list of hex colors.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Palette, Error> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        let mut palette = match extension.as_deref() {
+            Some("ase") => Palette::from_ase(&fs::read(path)?)?,
+            Some("gpl") => Palette::from_gpl(&fs::read_to_string(path)?)?,
+            Some("json") => Palette::from_lospec_json(&fs::read_to_string(path)?)?,
+            _ => Palette::from_hex_list(&fs::read_to_string(path)?)?,
+        };
+        if palette.name.is_empty() {
+            palette.name = name;
+        }
+        Ok(palette)
+    }
+
+    /// Parses a GIMP `.gpl` palette. Color names, which may contain anything,
+    /// are ignored.
+    pub fn from_gpl(s: &str) -> Result<Palette, Error> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some("GIMP Palette") {
+            return Err(Error::Format("missing GIMP Palette header".into()));
+        }
+        let mut palette = Palette::new("", Vec::new());
+        for line in lines.map(str::trim) {
+            if let Some(name) = line.strip_prefix("Name:") {
+                palette.name = name.trim().to_string();
+            } else if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+                continue;
+            } else {
+                let mut channels = line.split_whitespace().map(str::parse::<u8>);
+                match (channels.next(), channels.next(), channels.next()) {
+                    (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => {
+                        palette.colors.push(Color([r, g, b]))
+                    }
+                    _ => return Err(Error::Format(format!("invalid color line: {}", line))),
+                }
+            }
+        }
+        palette.non_empty()
+    }
+
+    /// Parses an Adobe Swatch Exchange `.ase` file. RGB, CMYK, Lab and gray
+    /// swatches are converted to sRGB.
+    pub fn from_ase(bytes: &[u8]) -> Result<Palette, Error> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != b"ASEF" {
+            return Err(Error::Format("missing ASEF header".into()));
+        }
+        reader.take(4)?; // version
+        let blocks = reader.u32()?;
+
+        let mut palette = Palette::new("", Vec::new());
+        for _ in 0..blocks {
+            let kind = reader.u16()?;
+            let len = reader.u32()? as usize;
+            let mut block = Reader(reader.take(len)?);
+            match kind {
+                // Group start: use the first group's name for the palette.
+                0xc001 if palette.name.is_empty() && len > 0 => palette.name = block.utf16()?,
+                0x0001 => {
+                    block.utf16()?;
+                    let model = block.take(4)?;
+                    let mut values = [0.0; 4];
+                    let count = match model {
+                        b"RGB " | b"LAB " => 3,
+                        b"CMYK" => 4,
+                        b"Gray" => 1,
+                        _ => return Err(Error::Format("unknown ASE color model".into())),
+                    };
+                    for value in values.iter_mut().take(count) {
+                        *value = f32::from_bits(block.u32()?);
+                    }
+                    let [a, b, c, d] = values;
+                    let rgb = match model {
+                        b"RGB " => [a, b, c],
+                        b"CMYK" => [
+                            (1.0 - a) * (1.0 - d),
+                            (1.0 - b) * (1.0 - d),
+                            (1.0 - c) * (1.0 - d),
+                        ],
+                        b"LAB " => lab_to_srgb(a * 100.0, b, c),
+                        _ => [a, a, a],
+                    };
+                    palette.colors.push(Color(
+                        rgb.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        palette.non_empty()
+    }
+
+    /// Parses hex colors separated by whitespace or commas, as in Lospec's
+    /// `.hex` files. Lines starting with `;` or `//` are comments.
+    pub fn from_hex_list(s: &str) -> Result<Palette, Error> {
+        let colors = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with(';') && !line.starts_with("//"))
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse().map_err(Error::Format))
+            .collect::<Result<_, _>>()?;
+        Palette::new("", colors).non_empty()
+    }
+
+    /// Parses Lospec's JSON export: `{"name": ..., "colors": ["rrggbb", ...]}`.
+    pub fn from_lospec_json(s: &str) -> Result<Palette, Error> {
+        #[derive(Deserialize)]
+        struct Lospec {
+            #[serde(default)]
+            name: String,
+            colors: Vec<String>,
+        }
+        let lospec: Lospec =
+            serde_json::from_str(s).map_err(|err| Error::Format(err.to_string()))?;
+        let colors = lospec
+            .colors
+            .iter()
+            .map(|color| color.parse().map_err(Error::Format))
+            .collect::<Result<_, _>>()?;
+        Palette::new(lospec.name, colors).non_empty()
+    }
+
+    fn non_empty(self) -> Result<Palette, Error> {
+        if self.colors.is_empty() {
+            Err(Error::Format("palette has no colors".into()))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+/// Converts CIE L*a*b* (D50, as Adobe uses) to sRGB in `0.0..=1.0`.
+fn lab_to_srgb(l: f32, a: f32, b: f32) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+    let f_inv = |t: f32| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            3.0 * (6.0f32 / 29.0).powi(2) * (t - 4.0 / 29.0)
+        }
+    };
+    let (x, y, z) = (0.9642 * f_inv(fx), f_inv(fy), 0.8251 * f_inv(fz));
+    // Bradford-adapted XYZ (D50) to linear sRGB.
+    let linear = [
+        3.1339 * x - 1.6169 * y - 0.4906 * z,
+        -0.9788 * x + 1.9161 * y + 0.0335 * z,
+        0.0719 * x - 0.2290 * y + 1.4052 * z,
+    ];
+    linear.map(|c| {
+        if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+/// Big-endian reads from a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::Format("unexpected end of file".into()));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A length-prefixed, null-terminated UTF-16 string.
+    fn utf16(&mut self) -> Result<String, Error> {
+        let len = self.u16()? as usize;
+        let units = (0..len)
+            .map(|_| self.u16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let units = units.strip_suffix(&[0]).unwrap_or(&units);
+        Ok(String::from_utf16_lossy(units))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::Format(msg) => write!(f, "invalid palette: {}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpl() {
+        let gpl = [
+            "GIMP Palette",
+            "Name: Test",
+            "Columns: 4",
+            "#",
+            "255   0   0\tRed: 255",
+            "  0 128 255 Blue",
+            "",
+            "16 16 16",
+        ]
+        .join("\n");
+        let palette = Palette::from_gpl(&gpl).unwrap();
+        assert_eq!(palette.name, "Test");
+        assert_eq!(
+            palette.colors,
+            [
+                Color([255, 0, 0]),
+                Color([0, 128, 255]),
+                Color([16, 16, 16])
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_gpl() {
+        assert!(Palette::from_gpl("255 0 0\n").is_err());
+        assert!(Palette::from_gpl("GIMP Palette\n255 0\n").is_err());
+        assert!(Palette::from_gpl("GIMP Palette\n256 0 0\n").is_err());
+        assert!(Palette::from_gpl("GIMP Palette\nName: Empty\n").is_err());
+    }
+
+    /// An ASE color block: a name, a color model and its values.
+    fn ase_color(model: &[u8; 4], values: &[f32]) -> Vec<u8> {
+        let mut block = vec![0, 2, 0, b'x', 0, 0];
+        block.extend_from_slice(model);
+        for value in values {
+            block.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+        block.extend_from_slice(&[0, 0]); // the color type
+        block
+    }
+
+    fn ase(blocks: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = b"ASEF\x00\x01\x00\x00".to_vec();
+        bytes.extend_from_slice(&(blocks.len() as u32).to_be_bytes());
+        for (kind, block) in blocks {
+            bytes.extend_from_slice(&kind.to_be_bytes());
+            bytes.extend_from_slice(&(block.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(block);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ase_models() {
+        // "Hi", length-prefixed and null-terminated, in UTF-16.
+        let group = vec![0, 3, 0, b'H', 0, b'i', 0, 0];
+        let bytes = ase(&[
+            (0xc001, group),
+            (0x0001, ase_color(b"RGB ", &[1.0, 0.5, 0.0])),
+            (0x0001, ase_color(b"CMYK", &[0.0, 1.0, 1.0, 0.0])),
+            (0x0001, ase_color(b"Gray", &[0.25])),
+            (0x0001, ase_color(b"LAB ", &[1.0, 0.0, 0.0])),
+            (0xc002, Vec::new()),
+        ]);
+        let palette = Palette::from_ase(&bytes).unwrap();
+        assert_eq!(palette.name, "Hi");
+        assert_eq!(
+            palette.colors,
+            [
+                Color([255, 128, 0]),
+                Color([255, 0, 0]),
+                Color([64, 64, 64]),
+                Color([255, 255, 255]),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_ase() {
+        assert!(Palette::from_ase(b"GIMP").is_err());
+        assert!(Palette::from_ase(&ase(&[])).is_err());
+        let unknown = ase(&[(0x0001, ase_color(b"HSV ", &[0.0, 0.0, 0.0]))]);
+        assert!(Palette::from_ase(&unknown).is_err());
+        let mut truncated = ase(&[(0x0001, ase_color(b"RGB ", &[1.0, 1.0, 1.0]))]);
+        truncated.truncate(truncated.len() - 6);
+        assert!(Palette::from_ase(&truncated).is_err());
+    }
+
+    #[test]
+    fn hex_list() {
+        let hex = "; paint.net palette\n// comment\nff0000\n#00ff00, 00f\n\n";
+        let palette = Palette::from_hex_list(hex).unwrap();
+        assert_eq!(
+            palette.colors,
+            [Color([255, 0, 0]), Color([0, 255, 0]), Color([0, 0, 255])]
+        );
+    }
+
+    #[test]
+    fn malformed_hex_list() {
+        assert!(Palette::from_hex_list("ff0000 nothex\n").is_err());
+        assert!(Palette::from_hex_list("ff00\n").is_err());
+        assert!(Palette::from_hex_list("; only a comment\n").is_err());
+    }
+
+    #[test]
+    fn lospec_json() {
+        let json = r#"{"name": "Two", "author": "", "colors": ["000000", "ffffff"]}"#;
+        let palette = Palette::from_lospec_json(json).unwrap();
+        assert_eq!(palette.name, "Two");
+        assert_eq!(palette.colors, [Color([0, 0, 0]), Color([255, 255, 255])]);
+    }
+
+    #[test]
+    fn malformed_lospec_json() {
+        assert!(Palette::from_lospec_json(r#"{"name": "No colors"}"#).is_err());
+        assert!(Palette::from_lospec_json(r#"{"colors": ["zzzzzz"]}"#).is_err());
+        assert!(Palette::from_lospec_json(r#"{"colors": []}"#).is_err());
+        assert!(Palette::from_lospec_json("[").is_err());
+    }
+}
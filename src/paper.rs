@@ -0,0 +1,112 @@
+//! Paper sizes for plotter and print output.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Paper {
+    #[default]
+    A4,
+    A3,
+    Letter,
+    /// Width and height in millimeters, portrait.
+    Custom([f32; 2]),
+}
+
+impl Paper {
+    pub const STANDARD: [Paper; 3] = [Paper::A4, Paper::A3, Paper::Letter];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Paper::A4 => "A4",
+            Paper::A3 => "A3",
+            Paper::Letter => "Letter",
+            Paper::Custom(_) => "Custom",
+        }
+    }
+
+    /// Width and height in millimeters, portrait.
+    pub fn size(self) -> [f32; 2] {
+        match self {
+            Paper::A4 => [210.0, 297.0],
+            Paper::A3 => [297.0, 420.0],
+            Paper::Letter => [215.9, 279.4],
+            Paper::Custom(size) => size,
+        }
+    }
+
+    /// Width and height in millimeters, turned to `orientation`.
+    pub fn oriented(self, orientation: Orientation) -> [f32; 2] {
+        let [short, long] = {
+            let [w, h] = self.size();
+            [w.min(h), w.max(h)]
+        };
+        match orientation {
+            Orientation::Portrait => [short, long],
+            Orientation::Landscape => [long, short],
+        }
+    }
+}
+
+impl fmt::Display for Paper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Paper::Custom([w, h]) => write!(f, "{}x{}", w, h),
+            paper => write!(f, "{}", paper.name().to_ascii_lowercase()),
+        }
+    }
+}
+
+/// Parses `a4`, `a3`, `letter` or a custom size in millimeters like `300x400`.
+impl FromStr for Paper {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(paper) = Paper::STANDARD
+            .into_iter()
+            .find(|paper| paper.name().eq_ignore_ascii_case(s))
+        {
+            return Ok(paper);
+        }
+        let invalid = || format!("invalid paper size: {}", s);
+        let (w, h) = s.split_once('x').ok_or_else(invalid)?;
+        let w: f32 = w.trim().parse().map_err(|_| invalid())?;
+        let h: f32 = h.trim().parse().map_err(|_| invalid())?;
+        if w <= 0.0 || h <= 0.0 {
+            return Err(invalid());
+        }
+        Ok(Paper::Custom([w, h]))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    #[default]
+    Portrait,
+    Landscape,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 2] = [Orientation::Portrait, Orientation::Landscape];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Orientation::Portrait => "portrait",
+            Orientation::Landscape => "landscape",
+        }
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Orientation::ALL
+            .into_iter()
+            .find(|orientation| orientation.name() == s)
+            .ok_or_else(|| format!("unknown orientation: {}", s))
+    }
+}
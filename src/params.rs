@@ -0,0 +1,287 @@
+//! Presets: everything needed to reproduce a composition, as TOML or JSON.
+
+use std::{error, fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{metadata, Layer, Layout, Occlusion, Schotter};
+
+/// A complete, serializable description of a composition.
+///
+/// The fields of [`Layout`] are stored at the top level, so a preset file
+/// reads like `size = 30.0`, `margin = 35.0` and so on, followed by one
+/// `[[layers]]` table per layer. Missing fields fall back to their defaults.
+/// Presets saved before layers existed hold a single [`Schotter`] at the top
+/// level, and load as one layer. Presets that [`Schotter::validate`] rejects
+/// fail to load.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ParamsRepr")]
+pub struct SchotterParams {
+    // Plain values must precede tables in TOML, so the layout goes first.
+    #[serde(flatten)]
+    pub layout: Layout,
+    /// Whether stones hide the outlines of the stones behind them.
+    pub occlusion: Occlusion,
+    /// Drawn in order, the first at the bottom.
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Deserialize)]
+struct ParamsRepr {
+    #[serde(flatten)]
+    layout: Layout,
+    #[serde(default)]
+    occlusion: Occlusion,
+    layers: Option<Vec<Layer>>,
+    #[serde(flatten)]
+    schotter: Schotter,
+}
+
+impl TryFrom<ParamsRepr> for SchotterParams {
+    type Error = String;
+
+    fn try_from(repr: ParamsRepr) -> Result<Self, Self::Error> {
+        let params = SchotterParams {
+            layout: repr.layout,
+            occlusion: repr.occlusion,
+            layers: repr
+                .layers
+                .unwrap_or_else(|| vec![Layer::new(repr.schotter)]),
+        };
+        for layer in &params.layers {
+            layer.schotter.validate()?;
+        }
+        Ok(params)
+    }
+}
+
+impl Default for SchotterParams {
+    fn default() -> Self {
+        SchotterParams::new(&Schotter::default(), &Layout::default())
+    }
+}
+
+impl SchotterParams {
+    /// A composition with a single layer.
+    pub fn new(schotter: &Schotter, layout: &Layout) -> Self {
+        SchotterParams {
+            layout: layout.clone(),
+            occlusion: Occlusion::default(),
+            layers: vec![Layer::new(schotter.clone())],
+        }
+    }
+
+    /// The bottom right corner of the visible layers, in grid units.
+    pub fn extent(&self) -> [f32; 2] {
+        self.layers
+            .iter()
+            .filter(|layer| layer.visible)
+            .map(Layer::extent)
+            .fold([0.0, 0.0], |[w, h], [x, y]| [w.max(x), h.max(y)])
+    }
+
+    /// The size of the composition, in pixels.
+    pub fn pixel_size(&self) -> [f32; 2] {
+        self.layout.pixel_size(self.extent())
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// Writes the preset to `path`, as JSON if it ends in `.json` and as TOML
+    /// otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let contents = if has_extension(path.as_ref(), "json") {
+            self.to_json()?
+        } else {
+            self.to_toml()?
+        };
+        Ok(fs::write(path, contents)?)
+    }
+
+    /// Reads a preset written by [`SchotterParams::save`], or the parameters
+    /// embedded in a PNG exported by this crate.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        if has_extension(path.as_ref(), "png") {
+            return metadata::extract_file(path);
+        }
+        let contents = fs::read_to_string(&path)?;
+        if has_extension(path.as_ref(), "json") {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        }
+    }
+}
+
+/// (De)serializes a seed as an integer, or as a string if it is above
+/// `i64::MAX`, which is as large as TOML integers go. Either form loads.
+pub(crate) mod seed {
+    use std::fmt;
+
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        match i64::try_from(*seed) {
+            Ok(seed) => serializer.serialize_i64(seed),
+            Err(_) => serializer.serialize_str(&seed.to_string()),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        deserializer.deserialize_any(SeedVisitor)
+    }
+
+    struct SeedVisitor;
+
+    impl<'de> de::Visitor<'de> for SeedVisitor {
+        type Value = u64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a non-negative integer or a string holding one")
+        }
+
+        fn visit_u64<E: de::Error>(self, seed: u64) -> Result<u64, E> {
+            Ok(seed)
+        }
+
+        fn visit_i64<E: de::Error>(self, seed: i64) -> Result<u64, E> {
+            u64::try_from(seed).map_err(|_| E::invalid_value(de::Unexpected::Signed(seed), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, seed: &str) -> Result<u64, E> {
+            seed.parse()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(seed), &self))
+        }
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    Json(serde_json::Error),
+    NotPng,
+    MissingParams,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => err.fmt(f),
+            Error::TomlDe(err) => err.fmt(f),
+            Error::TomlSer(err) => err.fmt(f),
+            Error::Json(err) => err.fmt(f),
+            Error::NotPng => write!(f, "not a valid PNG file"),
+            Error::MissingParams => write!(f, "no Schotter parameters found in PNG"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Error::TomlDe(err)
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Error::TomlSer(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_seed(seed: u64) -> SchotterParams {
+        SchotterParams::new(&Schotter::new(seed), &Layout::default())
+    }
+
+    #[test]
+    fn seeds_round_trip_through_toml() {
+        for seed in [0, 42, i64::MAX as u64, i64::MAX as u64 + 1, u64::MAX] {
+            let params = with_seed(seed);
+            let toml = params.to_toml().unwrap();
+            assert_eq!(SchotterParams::from_toml(&toml).unwrap(), params);
+        }
+    }
+
+    #[test]
+    fn seeds_round_trip_through_json() {
+        for seed in [0, i64::MAX as u64 + 1, u64::MAX] {
+            let params = with_seed(seed);
+            let json = params.to_json().unwrap();
+            assert_eq!(SchotterParams::from_json(&json).unwrap(), params);
+        }
+    }
+
+    #[test]
+    fn small_seeds_are_written_as_integers() {
+        let toml = with_seed(42).to_toml().unwrap();
+        assert!(toml.contains("seed = 42\n"), "{}", toml);
+        // TOML may quote the string either way.
+        let toml = with_seed(u64::MAX).to_toml().unwrap();
+        assert!(toml.contains("18446744073709551615"), "{}", toml);
+        assert!(!toml.contains("seed = 18446744073709551615"), "{}", toml);
+    }
+
+    #[test]
+    fn negative_seeds_are_rejected() {
+        assert!(SchotterParams::from_toml("[[layers]]\nseed = -1\n").is_err());
+    }
+
+    #[test]
+    fn oversized_grids_are_rejected() {
+        assert!(SchotterParams::from_toml("[[layers]]\ncols = 1000\nrows = 1000\n").is_ok());
+        for toml in ["[[layers]]\ncols = 70000\n", "cols = 12\nrows = 70000\n"] {
+            assert!(SchotterParams::from_toml(toml).is_err(), "{}", toml);
+        }
+        let json = r#"{"layers": [{"cols": 4294967295, "rows": 4294967295}]}"#;
+        assert!(SchotterParams::from_json(json).is_err());
+    }
+
+    #[test]
+    fn unusable_noise_is_rejected() {
+        for json in [
+            r#"{"layers": [{"noise_scale": 1e30}]}"#,
+            r#"{"layers": [{"noise_phase": -1e30}]}"#,
+        ] {
+            assert!(SchotterParams::from_json(json).is_err(), "{}", json);
+        }
+        assert!(SchotterParams::from_toml("[[layers]]\nnoise_scale = nan\n").is_err());
+    }
+}
@@ -0,0 +1,510 @@
+//! Vector PDF export, one composition per page.
+//!
+//! Pages are laid out like [`print`](crate::print) lays out PNGs, and carry a
+//! caption with the parameters that produced them, so a batch of seeds or a
+//! sweep over the factors prints as a contact sheet for review.
+
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    io::{self, Write as _},
+    path::Path,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{shape, BlendMode, Color, PrintSettings, Scene, SchotterParams};
+
+const PT_PER_MM: f32 = 72.0 / 25.4;
+/// Caption font size, in points.
+const FONT_SIZE: f32 = 7.0;
+
+/// The variations of a composition written as pages of one PDF.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Batch {
+    /// Just the composition.
+    #[default]
+    Single,
+    /// Consecutive seeds, starting with the layer's own.
+    Seeds { count: u32 },
+    /// Evenly spaced values of the layer's `disp_adj`.
+    Displacement { from: f32, to: f32, steps: u32 },
+    /// Evenly spaced values of the layer's `rot_adj`.
+    Rotation { from: f32, to: f32, steps: u32 },
+}
+
+impl Batch {
+    /// One of each kind, for pickers.
+    pub fn examples() -> [Batch; 4] {
+        [
+            Batch::Single,
+            Batch::Seeds { count: 12 },
+            Batch::Displacement {
+                from: 0.0,
+                to: 3.0,
+                steps: 7,
+            },
+            Batch::Rotation {
+                from: 0.0,
+                to: 3.0,
+                steps: 7,
+            },
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Batch::Single => "single",
+            Batch::Seeds { .. } => "seeds",
+            Batch::Displacement { .. } => "displacement",
+            Batch::Rotation { .. } => "rotation",
+        }
+    }
+
+    /// The compositions to print, varying the layer at index `layer`.
+    pub fn pages(self, params: &SchotterParams, layer: usize) -> Vec<SchotterParams> {
+        let vary = |count: u32, change: &dyn Fn(&mut SchotterParams, u32)| {
+            (0..count.max(1))
+                .map(|i| {
+                    let mut page = params.clone();
+                    if layer < page.layers.len() {
+                        change(&mut page, i);
+                    }
+                    page
+                })
+                .collect::<Vec<_>>()
+        };
+        let step = |from: f32, to: f32, steps: u32, i: u32| {
+            if steps > 1 {
+                from + (to - from) * i as f32 / (steps - 1) as f32
+            } else {
+                from
+            }
+        };
+        match self {
+            Batch::Single => vec![params.clone()],
+            Batch::Seeds { count } => vary(count, &|page, i| {
+                let schotter = &mut page.layers[layer].schotter;
+                schotter.seed = schotter.seed.wrapping_add(i as u64);
+            }),
+            Batch::Displacement { from, to, steps } => vary(steps, &|page, i| {
+                page.layers[layer].schotter.disp_adj = step(from, to, steps, i);
+            }),
+            Batch::Rotation { from, to, steps } => vary(steps, &|page, i| {
+                page.layers[layer].schotter.rot_adj = step(from, to, steps, i);
+            }),
+        }
+    }
+}
+
+/// Formats a batch the way [`Batch::from_str`] reads it.
+impl fmt::Display for Batch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Batch::Single => write!(f, "single"),
+            Batch::Seeds { count } => write!(f, "seeds:{}", count),
+            Batch::Displacement { from, to, steps } | Batch::Rotation { from, to, steps } => {
+                write!(f, "{}:{}..{}:{}", self.name(), from, to, steps)
+            }
+        }
+    }
+}
+
+/// Parses `single`, `seeds:<COUNT>`, `displacement:<FROM>..<TO>:<STEPS>` or
+/// `rotation:<FROM>..<TO>:<STEPS>`.
+impl FromStr for Batch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid batch: {}", s);
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        let sweep = || -> Option<(f32, f32, u32)> {
+            let (range, steps) = args.split_once(':')?;
+            let (from, to) = range.split_once("..")?;
+            Some((from.parse().ok()?, to.parse().ok()?, steps.parse().ok()?))
+        };
+        match name {
+            "single" if args.is_empty() => Ok(Batch::Single),
+            "seeds" => Ok(Batch::Seeds {
+                count: args.parse().map_err(|_| invalid())?,
+            }),
+            "displacement" => {
+                let (from, to, steps) = sweep().ok_or_else(invalid)?;
+                Ok(Batch::Displacement { from, to, steps })
+            }
+            "rotation" => {
+                let (from, to, steps) = sweep().ok_or_else(invalid)?;
+                Ok(Batch::Rotation { from, to, steps })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Writes `pages` as a PDF document, one composition per page, if `settings`
+/// are valid.
+pub fn to_pdf(pages: &[SchotterParams], settings: &PrintSettings) -> io::Result<Vec<u8>> {
+    settings
+        .validate()
+        .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
+    let mut pdf = Writer::default();
+    pdf.out.extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+
+    // Objects 1 to 4 are fixed; each page then takes two, itself and its
+    // content stream.
+    let page_id = |i: usize| 5 + 2 * i;
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", page_id(i)))
+        .collect();
+    pdf.object(1, "<< /Type /Catalog /Pages 2 0 R >>");
+    pdf.object(
+        2,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+    );
+    pdf.object(
+        3,
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    );
+    pdf.object(
+        4,
+        &format!(
+            "<< /Producer ({} {}) >>",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ),
+    );
+
+    for (i, params) in pages.iter().enumerate() {
+        let caption = caption(params, i, pages.len());
+        let page = Page::new(params, settings, &caption);
+        let [width, height] = page.size;
+        let bleed = settings.bleed * PT_PER_MM;
+        pdf.object(
+            page_id(i),
+            &format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {w:.2} {h:.2}] \
+                 /TrimBox [{b:.2} {b:.2} {tw:.2} {th:.2}] \
+                 /Resources << /Font << /F1 3 0 R >> /ExtGState << {gs} >> >> \
+                 /Contents {c} 0 R >>",
+                w = width,
+                h = height,
+                b = bleed,
+                tw = width - bleed,
+                th = height - bleed,
+                gs = page.graphics_states(),
+                c = page_id(i) + 1
+            ),
+        );
+        pdf.stream(page_id(i) + 1, page.content.as_bytes());
+    }
+
+    Ok(pdf.finish(4 + 2 * pages.len()))
+}
+
+/// Writes `pages` to `path` as a PDF file.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    pages: &[SchotterParams],
+    settings: &PrintSettings,
+) -> io::Result<()> {
+    fs::write(path, to_pdf(pages, settings)?)
+}
+
+/// The lines printed under a composition.
+fn caption(params: &SchotterParams, page: usize, pages: usize) -> Vec<String> {
+    let mut lines = vec![format!("Page {} of {}", page + 1, pages)];
+    for layer in params.layers.iter().filter(|layer| layer.visible) {
+        let schotter = &layer.schotter;
+        lines.push(format!(
+            "{}: seed {}, disp {:.2}, rot {:.2}, noise {}, falloff {} / {}, {}x{}, {}",
+            layer.name,
+            schotter.seed,
+            schotter.disp_adj,
+            schotter.rot_adj,
+            schotter.noise.name(),
+            schotter.disp_falloff,
+            schotter.rot_falloff,
+            schotter.cols,
+            schotter.rows,
+            shape::format_shapes(&schotter.shapes)
+        ));
+    }
+    lines
+}
+
+/// One page's content stream, in points with the origin at the bottom left.
+struct Page {
+    size: [f32; 2],
+    content: String,
+    /// The blend mode and fill opacity of each `/G<index>` graphics state.
+    states: Vec<(BlendMode, f32)>,
+}
+
+impl Page {
+    fn new(params: &SchotterParams, settings: &PrintSettings, caption: &[String]) -> Self {
+        let layout = settings.page_layout(params);
+        let size = layout.size.map(|mm| mm * PT_PER_MM);
+        let to_pt = |[x, y]: [f32; 2]| {
+            [
+                (layout.origin[0] + x * layout.stone_size) * PT_PER_MM,
+                size[1] - (layout.origin[1] + y * layout.stone_size) * PT_PER_MM,
+            ]
+        };
+        let mut page = Page {
+            size,
+            content: String::new(),
+            // The caption relies on `/G0` drawing normally.
+            states: vec![(BlendMode::Normal, 1.0)],
+        };
+        let c = &mut page.content;
+
+        writeln!(
+            c,
+            "{} rg 0 0 {:.2} {:.2} re f",
+            rgb(params.layout.background),
+            size[0],
+            size[1]
+        )
+        .unwrap();
+        writeln!(c, "0 j 0 J {:.3} w", settings.line_width * PT_PER_MM).unwrap();
+
+        let mut current = None;
+        for group in Scene::new(params).groups {
+            for item in &group.items {
+                for path in &item.paths {
+                    let fill = item.paint.fill.filter(|_| path.closed);
+                    let state = (group.blend, fill.map_or(1.0, |(_, opacity)| opacity));
+                    if current != Some(state) {
+                        let index = match page.states.iter().position(|s| *s == state) {
+                            Some(index) => index,
+                            None => {
+                                page.states.push(state);
+                                page.states.len() - 1
+                            }
+                        };
+                        writeln!(c, "/G{} gs", index).unwrap();
+                        current = Some(state);
+                    }
+                    write!(c, "{} RG ", rgb(item.paint.stroke)).unwrap();
+                    if let Some((color, _)) = fill {
+                        write!(c, "{} rg ", rgb(color)).unwrap();
+                    }
+                    for (i, &point) in path.points.iter().enumerate() {
+                        let [x, y] = to_pt(point);
+                        write!(c, "{:.2} {:.2} {} ", x, y, if i == 0 { "m" } else { "l" }).unwrap();
+                    }
+                    if path.closed {
+                        c.push_str("h ");
+                    }
+                    c.push_str(if fill.is_some() { "B\n" } else { "S\n" });
+                }
+            }
+        }
+
+        // The caption sits in the bottom margin, its last line lowest.
+        let left = (settings.bleed + settings.margin) * PT_PER_MM;
+        let bottom = (settings.bleed + settings.margin / 2.0) * PT_PER_MM;
+        writeln!(c, "/G0 gs").unwrap();
+        writeln!(c, "BT /F1 {} Tf 0.4 g", FONT_SIZE).unwrap();
+        for (i, line) in caption.iter().enumerate() {
+            let y = bottom + (caption.len() - 1 - i) as f32 * FONT_SIZE * 1.3;
+            writeln!(c, "1 0 0 1 {:.2} {:.2} Tm ({}) Tj", left, y, escape(line)).unwrap();
+        }
+        c.push_str("ET\n");
+        page
+    }
+
+    fn graphics_states(&self) -> String {
+        self.states
+            .iter()
+            .enumerate()
+            .map(|(i, (blend, opacity))| {
+                format!(
+                    "/G{} << /BM /{} /CA 1 /ca {:.3} >>",
+                    i,
+                    pdf_blend_mode(*blend),
+                    opacity
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn pdf_blend_mode(blend: BlendMode) -> &'static str {
+    match blend {
+        // PDF has no additive mode; screen is the closest.
+        BlendMode::Normal => "Normal",
+        BlendMode::Multiply => "Multiply",
+        BlendMode::Screen | BlendMode::Add => "Screen",
+        BlendMode::Darken => "Darken",
+        BlendMode::Lighten => "Lighten",
+    }
+}
+
+fn rgb(Color([r, g, b]): Color) -> String {
+    format!(
+        "{:.3} {:.3} {:.3}",
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0
+    )
+}
+
+/// Escapes text for a PDF string, replacing what Helvetica cannot show.
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            ' '..='~' => c.to_string(),
+            _ => String::from("?"),
+        })
+        .collect()
+}
+
+/// Numbered objects with their byte offsets, for the cross-reference table.
+#[derive(Default)]
+struct Writer {
+    out: Vec<u8>,
+    offsets: Vec<(usize, usize)>,
+}
+
+impl Writer {
+    fn object(&mut self, id: usize, body: &str) {
+        self.offsets.push((id, self.out.len()));
+        write!(self.out, "{} 0 obj\n{}\nendobj\n", id, body).unwrap();
+    }
+
+    fn stream(&mut self, id: usize, data: &[u8]) {
+        self.offsets.push((id, self.out.len()));
+        write!(
+            self.out,
+            "{} 0 obj\n<< /Length {} >>\nstream\n",
+            id,
+            data.len()
+        )
+        .unwrap();
+        self.out.extend_from_slice(data);
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    /// Appends the cross-reference table and trailer for objects `1..=last`.
+    fn finish(mut self, last: usize) -> Vec<u8> {
+        self.offsets.sort_unstable();
+        let xref = self.out.len();
+        write!(self.out, "xref\n0 {}\n0000000000 65535 f \n", last + 1).unwrap();
+        for (_, offset) in &self.offsets {
+            writeln!(self.out, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(
+            self.out,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 4 0 R >>\nstartxref\n{}\n%%EOF\n",
+            last + 1,
+            xref
+        )
+        .unwrap();
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layout, Schotter};
+
+    fn pdf(batch: Batch) -> Vec<u8> {
+        let params = SchotterParams::new(&Schotter::new(42), &Layout::default());
+        to_pdf(&batch.pages(&params, 0), &PrintSettings::default()).unwrap()
+    }
+
+    /// The object offsets listed in the cross-reference table, found through
+    /// `startxref`, in object number order from object 1.
+    fn xref_offsets(pdf: &[u8]) -> Vec<usize> {
+        // The header's binary comment is not UTF-8, but all after the table is.
+        let startxref = pdf.windows(10).rposition(|w| w == b"startxref\n").unwrap();
+        let tail = std::str::from_utf8(&pdf[startxref + 10..]).unwrap();
+        let xref: usize = tail.lines().next().unwrap().parse().unwrap();
+        let mut lines = std::str::from_utf8(&pdf[xref..]).unwrap().lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines
+            .next()
+            .unwrap()
+            .strip_prefix("0 ")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        (1..count)
+            .map(|_| {
+                let entry = lines.next().unwrap();
+                assert!(entry.ends_with(" 00000 n "), "{:?}", entry);
+                entry[..10].parse().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn xref_offsets_point_at_their_objects() {
+        for (batch, pages) in [(Batch::Single, 1), (Batch::Seeds { count: 3 }, 3)] {
+            let pdf = pdf(batch);
+            let offsets = xref_offsets(&pdf);
+            assert_eq!(offsets.len(), 4 + 2 * pages);
+            for (i, &offset) in offsets.iter().enumerate() {
+                let header = format!("{} 0 obj\n", i + 1);
+                assert!(
+                    pdf[offset..].starts_with(header.as_bytes()),
+                    "object {}",
+                    i + 1
+                );
+            }
+            let trailer = format!("/Size {} ", offsets.len() + 1);
+            assert!(String::from_utf8_lossy(&pdf).contains(&trailer));
+        }
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let pages = [SchotterParams::default()];
+        let settings = [
+            PrintSettings {
+                margin: 200.0,
+                ..Default::default()
+            },
+            PrintSettings {
+                bleed: -1.0,
+                ..Default::default()
+            },
+        ];
+        for settings in settings {
+            let err = to_pdf(&pages, &settings).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn page_count_matches_pages() {
+        for (batch, pages) in [
+            (Batch::Single, 1),
+            (Batch::Seeds { count: 5 }, 5),
+            (
+                Batch::Rotation {
+                    from: 0.0,
+                    to: 1.0,
+                    steps: 3,
+                },
+                3,
+            ),
+        ] {
+            let pdf = pdf(batch);
+            let text = String::from_utf8_lossy(&pdf);
+            assert!(text.contains(&format!("/Count {} >>", pages)), "{}", batch);
+            assert_eq!(text.matches("/Type /Page ").count(), pages, "{}", batch);
+        }
+    }
+}
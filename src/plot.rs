@@ -0,0 +1,499 @@
+//! Pen plotter output: HPGL and GRBL-style G-code.
+//!
+//! The composition is scaled to fit the paper and split into pens, one per
+//! layer or per stroke color. Within each pen the paths are reordered to cut
+//! down pen-up travel. Fills are left out; plotters only draw outlines.
+
+use std::{fmt::Write, fs, io, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Color, Orientation, Paper, Scene, SchotterParams};
+
+/// A polyline in millimeters, from the bottom left corner of the paper.
+pub type Polyline = Vec<[f32; 2]>;
+
+/// HPGL plotter units per millimeter.
+const HPGL_UNITS: f32 = 40.0;
+
+/// The most runs of paths [`optimize_order`] tries reversing. 2-opt takes
+/// time quadratic in the number of paths per pass, so large grids stop early.
+const TWO_OPT_BUDGET: usize = 20_000_000;
+
+/// What decides which pen draws a path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PenAssignment {
+    /// One pen per layer.
+    #[default]
+    Layer,
+    /// One pen per stroke color, in order of appearance.
+    Color,
+}
+
+impl PenAssignment {
+    pub const ALL: [PenAssignment; 2] = [PenAssignment::Layer, PenAssignment::Color];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PenAssignment::Layer => "layer",
+            PenAssignment::Color => "color",
+        }
+    }
+}
+
+impl FromStr for PenAssignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PenAssignment::ALL
+            .into_iter()
+            .find(|assignment| assignment.name() == s)
+            .ok_or_else(|| format!("unknown pen assignment: {}", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlotSettings {
+    pub paper: Paper,
+    pub orientation: Orientation,
+    /// Blank border around the drawing, in millimeters.
+    pub margin: f32,
+    /// G-code that lifts the pen.
+    pub pen_up: String,
+    /// G-code that lowers the pen.
+    pub pen_down: String,
+    /// Drawing speed, in millimeters per minute.
+    pub feed_rate: f32,
+    /// Speed of pen-up moves, in millimeters per minute. G-code only.
+    pub travel_rate: f32,
+    pub pen_by: PenAssignment,
+    /// How many pens the plotter holds. Further layers or colors reuse them.
+    pub pens: u32,
+    /// Reorder paths to shorten pen-up travel, instead of plotting them in
+    /// row-major order.
+    pub optimize: bool,
+}
+
+impl Default for PlotSettings {
+    fn default() -> Self {
+        PlotSettings {
+            paper: Paper::default(),
+            orientation: Orientation::default(),
+            margin: 15.0,
+            pen_up: String::from("G0 Z5"),
+            pen_down: String::from("G0 Z0"),
+            feed_rate: 1500.0,
+            travel_rate: 3000.0,
+            pen_by: PenAssignment::default(),
+            pens: 8,
+            optimize: true,
+        }
+    }
+}
+
+/// The paths one pen draws, in plotting order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pen {
+    /// From 1.
+    pub number: u32,
+    /// The stroke color of the first path given to the pen.
+    pub color: Color,
+    pub paths: Vec<Polyline>,
+}
+
+/// Splits a composition into pens, scaled and centered on the paper.
+pub fn pens(params: &SchotterParams, settings: &PlotSettings) -> Vec<Pen> {
+    let [width, height] = settings.paper.oriented(settings.orientation);
+    let [cols, rows] = params.extent();
+    if cols <= 0.0 || rows <= 0.0 {
+        return Vec::new();
+    }
+    let scale = ((width - 2.0 * settings.margin) / cols)
+        .min((height - 2.0 * settings.margin) / rows)
+        .max(0.0);
+    let left = (width - cols * scale) / 2.0;
+    let top = (height - rows * scale) / 2.0;
+    let to_paper = |[x, y]: [f32; 2]| [left + x * scale, height - top - y * scale];
+
+    // Pens are numbered in order of first use, so layers and colors that draw
+    // nothing do not take one.
+    let pen_count = settings.pens.max(1);
+    let mut layers = Vec::new();
+    let mut colors = Vec::new();
+    let mut pens: Vec<Pen> = Vec::new();
+    for group in Scene::new(params).groups {
+        for item in &group.items {
+            let polylines: Vec<Polyline> = item
+                .paths
+                .iter()
+                .filter(|path| path.points.len() >= 2)
+                .map(|path| {
+                    let mut polyline: Polyline = path.points.iter().map(|&p| to_paper(p)).collect();
+                    if path.closed {
+                        polyline.push(polyline[0]);
+                    }
+                    polyline
+                })
+                .collect();
+            if polylines.is_empty() {
+                continue;
+            }
+            let index = match settings.pen_by {
+                PenAssignment::Layer => first_use(&mut layers, group.layer),
+                PenAssignment::Color => first_use(&mut colors, item.paint.stroke),
+            };
+            let number = index as u32 % pen_count + 1;
+            let pen = match pens.iter().position(|pen| pen.number == number) {
+                Some(i) => &mut pens[i],
+                None => {
+                    pens.push(Pen {
+                        number,
+                        color: item.paint.stroke,
+                        paths: Vec::new(),
+                    });
+                    pens.last_mut().unwrap()
+                }
+            };
+            pen.paths.extend(polylines);
+        }
+    }
+
+    pens.sort_by_key(|pen| pen.number);
+    if settings.optimize {
+        for pen in &mut pens {
+            optimize_order(&mut pen.paths, [0.0, 0.0]);
+        }
+    }
+    pens
+}
+
+/// The index of `value` in `seen`, which it is added to if it is new.
+fn first_use<T: PartialEq>(seen: &mut Vec<T>, value: T) -> usize {
+    match seen.iter().position(|seen| *seen == value) {
+        Some(i) => i,
+        None => {
+            seen.push(value);
+            seen.len() - 1
+        }
+    }
+}
+
+/// Reorders `paths` to shorten the pen-up travel between them, starting from
+/// `start`: a nearest-neighbor tour, then 2-opt until it stops improving or
+/// has spent its budget of [`TWO_OPT_BUDGET`] tries.
+///
+/// Paths may be reversed, and closed ones may start at any of their points.
+pub fn optimize_order(paths: &mut Vec<Polyline>, start: [f32; 2]) {
+    // Nearest neighbor, picking the best entry point of each path.
+    let mut remaining = std::mem::take(paths);
+    let mut position = start;
+    while !remaining.is_empty() {
+        let mut best = (f32::INFINITY, 0, 0);
+        for (i, path) in remaining.iter().enumerate() {
+            let entries = if is_closed(path) {
+                0..path.len() - 1
+            } else {
+                0..1
+            };
+            for entry in entries.chain([path.len() - 1]) {
+                let d = distance(position, path[entry]);
+                if d < best.0 {
+                    best = (d, i, entry);
+                }
+            }
+        }
+        let (_, i, entry) = best;
+        let mut path = remaining.swap_remove(i);
+        if entry == path.len() - 1 {
+            path.reverse();
+        } else if entry > 0 {
+            path.pop();
+            path.rotate_left(entry);
+            path.push(path[0]);
+        }
+        position = *path.last().unwrap();
+        paths.push(path);
+    }
+
+    // 2-opt: reversing a run of paths also reverses each path in it, which
+    // only changes the travel into and out of the run.
+    let n = paths.len();
+    let mut order: Vec<(usize, bool)> = (0..n).map(|i| (i, false)).collect();
+    let ends = |(i, reversed): (usize, bool)| {
+        let path = &paths[i];
+        let (first, last) = (path[0], path[path.len() - 1]);
+        if reversed {
+            (last, first)
+        } else {
+            (first, last)
+        }
+    };
+    let mut budget = TWO_OPT_BUDGET;
+    'passes: for _ in 0..100 {
+        let mut improved = false;
+        for i in 0..n {
+            let before = if i == 0 { start } else { ends(order[i - 1]).1 };
+            for j in i..n {
+                if budget == 0 {
+                    break 'passes;
+                }
+                budget -= 1;
+                let (first, _) = ends(order[i]);
+                let (_, last) = ends(order[j]);
+                let after = order.get(j + 1).map(|&next| ends(next).0);
+                let old = distance(before, first) + after.map_or(0.0, |a| distance(last, a));
+                let new = distance(before, last) + after.map_or(0.0, |a| distance(first, a));
+                if new < old - 1e-4 {
+                    order[i..=j].reverse();
+                    for entry in &mut order[i..=j] {
+                        entry.1 = !entry.1;
+                    }
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let mut unordered: Vec<Option<Polyline>> =
+        std::mem::take(paths).into_iter().map(Some).collect();
+    for (i, reversed) in order {
+        let mut path = unordered[i].take().unwrap();
+        if reversed {
+            path.reverse();
+        }
+        paths.push(path);
+    }
+}
+
+fn is_closed(path: &Polyline) -> bool {
+    path.len() > 2 && distance(path[0], path[path.len() - 1]) < 1e-4
+}
+
+fn distance([x1, y1]: [f32; 2], [x2, y2]: [f32; 2]) -> f32 {
+    (x2 - x1).hypot(y2 - y1)
+}
+
+/// Renders a composition as HPGL, in plotter units of 0.025 mm.
+pub fn to_hpgl(params: &SchotterParams, settings: &PlotSettings) -> String {
+    let unit = |v: f32| (v * HPGL_UNITS).round() as i32;
+    let mut hpgl = String::from("IN;\n");
+    // VS takes centimeters per second.
+    writeln!(hpgl, "VS{};", (settings.feed_rate / 600.0).max(1.0).round()).unwrap();
+    for pen in pens(params, settings) {
+        writeln!(hpgl, "SP{};", pen.number).unwrap();
+        for path in &pen.paths {
+            let [x, y] = path[0];
+            write!(hpgl, "PU{},{};PD", unit(x), unit(y)).unwrap();
+            for (i, &[x, y]) in path[1..].iter().enumerate() {
+                if i > 0 {
+                    hpgl.push(',');
+                }
+                write!(hpgl, "{},{}", unit(x), unit(y)).unwrap();
+            }
+            hpgl.push_str(";\n");
+        }
+    }
+    hpgl.push_str("PU;SP0;\n");
+    hpgl
+}
+
+/// Renders a composition as G-code for GRBL, in millimeters. The program
+/// pauses with `M0` before each pen when more than one is used.
+pub fn to_gcode(params: &SchotterParams, settings: &PlotSettings) -> String {
+    let pens = pens(params, settings);
+    let mut gcode = String::new();
+    writeln!(
+        gcode,
+        "; {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    gcode.push_str("G21 ; millimeters\nG90 ; absolute coordinates\n");
+    writeln!(gcode, "{}", settings.pen_up).unwrap();
+    for pen in &pens {
+        if pens.len() > 1 {
+            writeln!(gcode, "M0 ; load pen {} ({})", pen.number, pen.color).unwrap();
+        }
+        for path in &pen.paths {
+            let [x, y] = path[0];
+            writeln!(gcode, "G1 X{:.3} Y{:.3} F{}", x, y, settings.travel_rate).unwrap();
+            writeln!(gcode, "{}", settings.pen_down).unwrap();
+            for (i, &[x, y]) in path[1..].iter().enumerate() {
+                write!(gcode, "G1 X{:.3} Y{:.3}", x, y).unwrap();
+                if i == 0 {
+                    write!(gcode, " F{}", settings.feed_rate).unwrap();
+                }
+                gcode.push('\n');
+            }
+            writeln!(gcode, "{}", settings.pen_up).unwrap();
+        }
+    }
+    writeln!(gcode, "G1 X0 Y0 F{}", settings.travel_rate).unwrap();
+    gcode.push_str("M2\n");
+    gcode
+}
+
+/// Writes a composition to `path` as HPGL if it ends in `.hpgl` or `.plt`,
+/// and as G-code otherwise.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    params: &SchotterParams,
+    settings: &PlotSettings,
+) -> io::Result<()> {
+    let hpgl = path
+        .as_ref()
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("hpgl") || ext.eq_ignore_ascii_case("plt"));
+    let contents = if hpgl {
+        to_hpgl(params, settings)
+    } else {
+        to_gcode(params, settings)
+    };
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layer, Schotter};
+
+    /// A composition of small grids, one layer per stroke color.
+    fn layers(strokes: &[Color]) -> SchotterParams {
+        let mut params = SchotterParams {
+            layers: Vec::new(),
+            ..Default::default()
+        };
+        for &stroke in strokes {
+            let mut schotter = Schotter {
+                cols: 2,
+                rows: 2,
+                ..Default::default()
+            };
+            schotter.style.stroke = stroke;
+            params.layers.push(Layer::new(schotter));
+        }
+        params
+    }
+
+    fn travel(paths: &[Polyline], start: [f32; 2]) -> f32 {
+        let mut position = start;
+        let mut total = 0.0;
+        for path in paths {
+            total += distance(position, path[0]);
+            position = path[path.len() - 1];
+        }
+        total
+    }
+
+    #[test]
+    fn optimizing_never_adds_travel() {
+        // Segments along a line, shuffled and pointing the wrong way.
+        let mut paths: Vec<Polyline> = vec![
+            vec![[5.0, 0.0], [4.0, 0.0]],
+            vec![[1.0, 0.0], [0.0, 0.0]],
+            vec![[3.0, 0.0], [2.0, 0.0]],
+        ];
+        let before = travel(&paths, [0.0, 0.0]);
+        optimize_order(&mut paths, [0.0, 0.0]);
+        let after = travel(&paths, [0.0, 0.0]);
+        assert!(after <= before, "{} > {}", after, before);
+        assert_eq!(after, 2.0);
+
+        let params = layers(&[Color::BLACK]);
+        let mut paths = pens(
+            &params,
+            &PlotSettings {
+                optimize: false,
+                ..Default::default()
+            },
+        )
+        .remove(0)
+        .paths;
+        let before = travel(&paths, [0.0, 0.0]);
+        let points: usize = paths.iter().map(Vec::len).sum();
+        optimize_order(&mut paths, [0.0, 0.0]);
+        assert!(travel(&paths, [0.0, 0.0]) <= before);
+        assert_eq!(paths.len(), 4);
+        assert_eq!(paths.iter().map(Vec::len).sum::<usize>(), points);
+    }
+
+    #[test]
+    fn pens_are_numbered_by_first_use() {
+        let red = Color([255, 0, 0]);
+        let blue = Color([0, 0, 255]);
+        let mut params = layers(&[red, red, blue, red]);
+        // Neither a hidden nor an empty layer takes a pen.
+        params.layers[0].visible = false;
+        params.layers[1].schotter.cols = 0;
+        let settings = PlotSettings::default();
+
+        let by_layer = pens(&params, &settings);
+        let numbers: Vec<_> = by_layer.iter().map(|pen| pen.number).collect();
+        assert_eq!(numbers, [1, 2]);
+        assert_eq!(by_layer[0].color, blue);
+
+        let by_color = pens(
+            &params,
+            &PlotSettings {
+                pen_by: PenAssignment::Color,
+                ..settings.clone()
+            },
+        );
+        let colors: Vec<_> = by_color.iter().map(|pen| (pen.number, pen.color)).collect();
+        assert_eq!(colors, [(1, blue), (2, red)]);
+
+        // Further layers reuse the pens the plotter holds.
+        let shared = pens(
+            &params,
+            &PlotSettings {
+                pens: 1,
+                ..settings
+            },
+        );
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].paths.len(), 8);
+    }
+
+    #[test]
+    fn hpgl_selects_pens_and_moves() {
+        let params = layers(&[Color::BLACK, Color::WHITESMOKE]);
+        let hpgl = to_hpgl(&params, &PlotSettings::default());
+        assert!(hpgl.starts_with("IN;\nVS3;\nSP1;\nPU"), "{}", hpgl);
+        assert!(hpgl.contains("\nSP2;\nPU"), "{}", hpgl);
+        assert!(hpgl.ends_with(";\nPU;SP0;\n"), "{}", hpgl);
+        // One pen-up move and one pen-down run per stone.
+        assert_eq!(hpgl.matches(";PD").count(), 8);
+        let one = to_hpgl(&layers(&[Color::BLACK]), &PlotSettings::default());
+        assert!(!one.contains("SP2;"), "{}", one);
+    }
+
+    #[test]
+    fn gcode_pauses_for_pens_and_moves() {
+        let settings = PlotSettings::default();
+        let gcode = to_gcode(&layers(&[Color::BLACK, Color::WHITESMOKE]), &settings);
+        assert!(gcode.contains("G21 ; millimeters\nG90 ; absolute coordinates\nG0 Z5\n"));
+        assert!(
+            gcode.contains("M0 ; load pen 1 (#000000)\nG1 X"),
+            "{}",
+            gcode
+        );
+        assert!(
+            gcode.contains("M0 ; load pen 2 (#f5f5f5)\nG1 X"),
+            "{}",
+            gcode
+        );
+        assert!(gcode.contains(" F3000\nG0 Z0\nG1 X"), "{}", gcode);
+        assert!(gcode.contains(" F1500\n"), "{}", gcode);
+        assert_eq!(gcode.matches("G0 Z0\n").count(), 8);
+        assert!(gcode.ends_with("G0 Z5\nG1 X0 Y0 F3000\nM2\n"), "{}", gcode);
+
+        let one = to_gcode(&layers(&[Color::BLACK]), &settings);
+        assert!(!one.contains("M0"), "{}", one);
+    }
+}
@@ -0,0 +1,158 @@
+//! High resolution PNG output sized for printing on paper.
+//!
+//! Unlike [`raster::render_at`], which scales the window's layout, this fits
+//! the composition onto a page: the stones grow as large as the margins
+//! allow, and strokes keep a fixed width in millimeters at any DPI.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tiny_skia::Pixmap;
+
+use crate::{metadata, raster, Orientation, Paper, SchotterParams};
+
+const MM_PER_INCH: f32 = 25.4;
+
+/// The highest resolution a page is rendered at.
+pub const MAX_DPI: f32 = 2400.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrintSettings {
+    pub paper: Paper,
+    pub orientation: Orientation,
+    pub dpi: f32,
+    /// Blank border inside the trimmed page, in millimeters.
+    pub margin: f32,
+    /// Background added beyond the trimmed page on every side, in
+    /// millimeters, for the printer to cut off.
+    pub bleed: f32,
+    /// Stroke width, in millimeters.
+    pub line_width: f32,
+}
+
+impl Default for PrintSettings {
+    fn default() -> Self {
+        PrintSettings {
+            paper: Paper::default(),
+            orientation: Orientation::default(),
+            dpi: 300.0,
+            margin: 20.0,
+            bleed: 0.0,
+            line_width: 0.5,
+        }
+    }
+}
+
+/// Where a composition lands on the page, in millimeters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageLayout {
+    /// The page size, bleed included.
+    pub size: [f32; 2],
+    /// The side of a stone.
+    pub stone_size: f32,
+    /// The top left corner of the grid, from the top left of the bleed.
+    pub origin: [f32; 2],
+}
+
+impl PrintSettings {
+    /// Checks that the settings describe a page that can be rendered: a
+    /// resolution up to [`MAX_DPI`], a margin that leaves room on the paper,
+    /// and no negative bleed or stroke width.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.dpi > 0.0 && self.dpi <= MAX_DPI) {
+            return Err(format!(
+                "DPI must be above 0 and at most {}: {}",
+                MAX_DPI, self.dpi
+            ));
+        }
+        let [width, height] = self.paper.oriented(self.orientation);
+        if !(self.margin >= 0.0 && 2.0 * self.margin < width.min(height)) {
+            return Err(format!(
+                "margin must leave room on the paper: {} mm",
+                self.margin
+            ));
+        }
+        if !(self.bleed >= 0.0 && self.bleed.is_finite()) {
+            return Err(format!("bleed must not be negative: {} mm", self.bleed));
+        }
+        if !(self.line_width >= 0.0 && self.line_width.is_finite()) {
+            return Err(format!(
+                "line width must not be negative: {} mm",
+                self.line_width
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn pixels_per_mm(&self) -> f32 {
+        self.dpi / MM_PER_INCH
+    }
+
+    /// The image size in pixels of a page laid out by
+    /// [`PrintSettings::page_layout`].
+    pub fn pixel_size(&self, page: &PageLayout) -> [u32; 2] {
+        page.size
+            .map(|mm| (mm * self.pixels_per_mm()).round() as u32)
+    }
+
+    /// Fits the visible layers of `params` inside the margins, centered.
+    pub fn page_layout(&self, params: &SchotterParams) -> PageLayout {
+        let [width, height] = self.paper.oriented(self.orientation);
+        let [cols, rows] = params.extent();
+        let stone_size = if cols > 0.0 && rows > 0.0 {
+            ((width - 2.0 * self.margin) / cols)
+                .min((height - 2.0 * self.margin) / rows)
+                .max(0.0)
+        } else {
+            0.0
+        };
+        PageLayout {
+            size: [width + 2.0 * self.bleed, height + 2.0 * self.bleed],
+            stone_size,
+            origin: [
+                self.bleed + (width - cols * stone_size) / 2.0,
+                self.bleed + (height - rows * stone_size) / 2.0,
+            ],
+        }
+    }
+}
+
+/// Rasterizes a composition onto a page as `settings` describe, if they are
+/// valid.
+pub fn render(params: &SchotterParams, settings: &PrintSettings) -> io::Result<Pixmap> {
+    settings
+        .validate()
+        .map_err(|msg| io::Error::new(io::ErrorKind::InvalidInput, msg))?;
+    let page = settings.page_layout(params);
+    let scale = settings.pixels_per_mm();
+    let mut scaled = params.clone();
+    scaled.layout.size = page.stone_size * scale;
+    scaled.layout.margin = 0.0;
+    scaled.layout.line_width = if page.stone_size > 0.0 {
+        settings.line_width / page.stone_size
+    } else {
+        0.0
+    };
+    let [width, height] = settings.pixel_size(&page);
+    raster::render_canvas(&scaled, width, height, page.origin.map(|mm| mm * scale))
+}
+
+/// Encodes a page as PNG bytes, with the composition's parameters and the
+/// print resolution embedded.
+pub fn to_png(params: &SchotterParams, settings: &PrintSettings) -> io::Result<Vec<u8>> {
+    let png = render(params, settings)?
+        .encode_png()
+        .map_err(io::Error::other)?;
+    let png = metadata::embed(&png, params).map_err(io::Error::other)?;
+    metadata::set_dpi(&png, settings.dpi).map_err(io::Error::other)
+}
+
+/// Writes a page to `path` as a PNG file.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    params: &SchotterParams,
+    settings: &PrintSettings,
+) -> io::Result<()> {
+    fs::write(path, to_png(params, settings)?)
+}
@@ -0,0 +1,332 @@
+//! Noise sources: where the random displacement and rotation come from.
+
+use std::{
+    f32::consts::{PI, TAU},
+    str::FromStr,
+};
+
+use noise::{NoiseFn, Perlin, Simplex, Value};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// The randomized properties of a stone.
+///
+/// Each attribute is hashed into its own random values, so the discriminants
+/// must never change: new attributes get new numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attribute {
+    XOffset = 0,
+    YOffset = 1,
+    Rotation = 2,
+    /// The pick from the color list in random color mode.
+    Color = 3,
+    /// The order stones set off in a randomly staggered transition.
+    Stagger = 4,
+}
+
+/// A well mixed 64-bit value that depends only on the seed, the stone's grid
+/// position and the attribute, so adding stones or attributes does not change
+/// the values of the others.
+pub fn stone_hash(seed: u64, col: u32, row: u32, attribute: Attribute) -> u64 {
+    [col as u64, row as u64, attribute as u64]
+        .into_iter()
+        .fold(mix(seed), |hash, value| mix(hash ^ value))
+}
+
+/// A value in `0.0..1.0` from the top 24 bits of `hash`.
+pub fn unit(hash: u64) -> f32 {
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// The SplitMix64 finalizer.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub trait NoiseSource {
+    /// A value in `-amplitude..amplitude` for one attribute of the stone at
+    /// `col`, `row`.
+    fn sample(&mut self, col: u32, row: u32, attribute: Attribute, amplitude: f32) -> f32;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Noise {
+    /// Independent uniform samples for every stone, as in Nees' original.
+    #[default]
+    Uniform,
+    /// Independent samples clustered around zero.
+    Gaussian,
+    Perlin,
+    Simplex,
+    Value,
+}
+
+impl Noise {
+    /// The largest `scale` a preset or the command line may set.
+    pub const MAX_SCALE: f32 = 100.0;
+    /// The largest `phase`, either way, a preset or the command line may set.
+    pub const MAX_PHASE: f32 = 1e6;
+
+    pub const ALL: [Noise; 5] = [
+        Noise::Uniform,
+        Noise::Gaussian,
+        Noise::Perlin,
+        Noise::Simplex,
+        Noise::Value,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Noise::Uniform => "uniform",
+            Noise::Gaussian => "gaussian",
+            Noise::Perlin => "perlin",
+            Noise::Simplex => "simplex",
+            Noise::Value => "value",
+        }
+    }
+
+    /// Whether neighboring stones get similar values, so `scale` matters.
+    pub fn is_coherent(self) -> bool {
+        matches!(self, Noise::Perlin | Noise::Simplex | Noise::Value)
+    }
+
+    /// Creates a source seeded with `seed`. Coherent noise is sampled at the
+    /// stone's grid coordinate times `scale`.
+    ///
+    /// Uniform and Gaussian noise hash every value from the stone's position,
+    /// unless `sequential` asks for the single `StdRng` stream that earlier
+    /// versions drew from in row-major order.
+    ///
+    /// `phase` moves every value smoothly at once: coherent noise is sampled
+    /// further along its third axis, and hashed noise blends towards the
+    /// values of the following seeds, reaching seed `seed + n` at phase `n`.
+    /// Sequential noise ignores it.
+    pub fn source(
+        self,
+        seed: u64,
+        scale: f32,
+        phase: f32,
+        sequential: bool,
+    ) -> Box<dyn NoiseSource> {
+        let noise_seed = (seed ^ (seed >> 32)) as u32;
+        match self {
+            Noise::Uniform if sequential => Box::new(Sequential(StdRng::seed_from_u64(seed))),
+            Noise::Gaussian if sequential => {
+                Box::new(SequentialGaussian(StdRng::seed_from_u64(seed)))
+            }
+            Noise::Uniform => Box::new(Uniform { seed, phase }),
+            Noise::Gaussian => Box::new(Gaussian { seed, phase }),
+            Noise::Perlin => Box::new(Coherent::new(Perlin::new(noise_seed), scale, phase)),
+            Noise::Simplex => Box::new(Coherent::new(Simplex::new(noise_seed), scale, phase)),
+            Noise::Value => Box::new(Coherent::new(Value::new(noise_seed), scale, phase)),
+        }
+    }
+}
+
+impl FromStr for Noise {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Noise::ALL
+            .into_iter()
+            .find(|noise| noise.name() == s)
+            .ok_or_else(|| format!("unknown noise: {}", s))
+    }
+}
+
+/// Blends the values that `value` makes from the hashes of the seeds either
+/// side of `phase`.
+fn phased(seed: u64, phase: f32, value: impl Fn(u64) -> f32) -> f32 {
+    let whole = phase.floor();
+    let t = phase - whole;
+    let seed = seed.wrapping_add(whole as i64 as u64);
+    let a = value(seed);
+    if t == 0.0 {
+        return a;
+    }
+    a + (value(seed.wrapping_add(1)) - a) * t
+}
+
+struct Uniform {
+    seed: u64,
+    phase: f32,
+}
+
+impl NoiseSource for Uniform {
+    fn sample(&mut self, col: u32, row: u32, attribute: Attribute, amplitude: f32) -> f32 {
+        phased(self.seed, self.phase, |seed| {
+            let u = unit(stone_hash(seed, col, row, attribute));
+            -amplitude + 2.0 * amplitude * u
+        })
+    }
+}
+
+struct Gaussian {
+    seed: u64,
+    phase: f32,
+}
+
+impl NoiseSource for Gaussian {
+    fn sample(&mut self, col: u32, row: u32, attribute: Attribute, amplitude: f32) -> f32 {
+        phased(self.seed, self.phase, |seed| {
+            let hash = stone_hash(seed, col, row, attribute);
+            gaussian(unit(hash), unit(hash << 24), amplitude)
+        })
+    }
+}
+
+/// Draws from a single stream, so the values depend on the sampling order.
+struct Sequential(StdRng);
+
+impl NoiseSource for Sequential {
+    fn sample(&mut self, _col: u32, _row: u32, _attribute: Attribute, amplitude: f32) -> f32 {
+        self.0.gen_range(-amplitude..amplitude)
+    }
+}
+
+struct SequentialGaussian(StdRng);
+
+impl NoiseSource for SequentialGaussian {
+    fn sample(&mut self, _col: u32, _row: u32, _attribute: Attribute, amplitude: f32) -> f32 {
+        let u1 = self.0.gen();
+        let u2 = self.0.gen();
+        gaussian(u1, u2, amplitude)
+    }
+}
+
+/// Box-Muller transform of two uniform values in `0.0..1.0`, with a standard
+/// deviation of a third of the amplitude.
+fn gaussian(u1: f32, u2: f32, amplitude: f32) -> f32 {
+    let z = (-2.0 * (1.0 - u1).ln()).sqrt() * (TAU * u2).cos();
+    (z * amplitude / 3.0).clamp(-amplitude, amplitude)
+}
+
+/// How far from the origin coherent noise is sampled.
+const MAX_COORDINATE: f64 = 1e12;
+
+struct Coherent<N> {
+    noise: N,
+    scale: f64,
+    phase: f64,
+}
+
+impl<N> Coherent<N> {
+    fn new(noise: N, scale: f32, phase: f32) -> Self {
+        Coherent {
+            noise,
+            scale: scale as f64,
+            phase: phase as f64,
+        }
+    }
+}
+
+impl<N: NoiseFn<f64, 3>> NoiseSource for Coherent<N> {
+    fn sample(&mut self, col: u32, row: u32, attribute: Attribute, amplitude: f32) -> f32 {
+        // Each attribute reads its own slice of the noise field, far enough
+        // apart to be unrelated. Sampling cell centers avoids the lattice
+        // points where gradient noise is always zero. Moving one unit along
+        // the slice changes the values about as much as a new seed would.
+        let z = attribute as u32 as f64 * 16.0 + PI as f64 + self.phase;
+        let x = (col as f64 + 0.5) * self.scale;
+        let y = (row as f64 + 0.5) * self.scale;
+        // The noise functions panic on points too far out to index their
+        // lattice, which unchecked scales and animated phases could reach.
+        if ![x, y, z].iter().all(|v| v.abs() < MAX_COORDINATE) {
+            return 0.0;
+        }
+        (self.noise.get([x, y, z]) as f32 * amplitude).clamp(-amplitude, amplitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_is_splitmix64() {
+        // The first output of SplitMix64 seeded with zero.
+        assert_eq!(mix(0), 0xe220_a839_7b1d_cdaf);
+    }
+
+    /// Saved seeds must keep their look, so these values may never change.
+    #[test]
+    fn stone_hashes_are_stable() {
+        let cases = [
+            (0, 0, 0, Attribute::XOffset, 0x2130_748a_aac8_0268),
+            (42, 3, 7, Attribute::YOffset, 0x4748_39d8_521f_dc6c),
+            (42, 3, 7, Attribute::Rotation, 0x4d2d_5eff_f94c_3f48),
+            (1234567, 11, 21, Attribute::Color, 0xc730_c5ba_e015_4f75),
+            (u64::MAX, 59, 59, Attribute::Stagger, 0x5e01_e036_b575_d144),
+        ];
+        for (seed, col, row, attribute, hash) in cases {
+            assert_eq!(
+                stone_hash(seed, col, row, attribute),
+                hash,
+                "{:?}",
+                (seed, col, row, attribute)
+            );
+        }
+    }
+
+    #[test]
+    fn coherent_noise_survives_extreme_points() {
+        for noise in [Noise::Perlin, Noise::Simplex, Noise::Value] {
+            for (scale, phase) in [
+                (f32::NAN, 0.0),
+                (1e30, 0.0),
+                (0.2, 1e30),
+                (0.2, f32::INFINITY),
+            ] {
+                let value =
+                    noise
+                        .source(7, scale, phase, false)
+                        .sample(3, 4, Attribute::XOffset, 0.5);
+                assert_eq!(value, 0.0, "{}", noise.name());
+            }
+        }
+    }
+
+    #[test]
+    fn unit_uses_the_top_bits() {
+        assert_eq!(unit(0), 0.0);
+        assert_eq!(unit(1 << 63), 0.5);
+        assert!(unit(u64::MAX) < 1.0);
+        assert_eq!(unit(0xffff_ff00_0000_0000), unit(u64::MAX));
+    }
+
+    #[test]
+    fn hashed_noise_ignores_the_sampling_order() {
+        for noise in [Noise::Uniform, Noise::Gaussian] {
+            let mut forward = noise.source(42, 0.2, 0.0, false);
+            let mut backward = noise.source(42, 0.2, 0.0, false);
+            let stones: Vec<_> = (0..5)
+                .flat_map(|row| (0..4).map(move |col| (col, row)))
+                .collect();
+            let a: Vec<_> = stones
+                .iter()
+                .map(|&(col, row)| forward.sample(col, row, Attribute::Rotation, 1.0))
+                .collect();
+            let mut b: Vec<_> = stones
+                .iter()
+                .rev()
+                .map(|&(col, row)| backward.sample(col, row, Attribute::Rotation, 1.0))
+                .collect();
+            b.reverse();
+            assert_eq!(a, b, "{}", noise.name());
+        }
+    }
+
+    #[test]
+    fn whole_phases_step_through_seeds() {
+        let mut phased = Noise::Uniform.source(42, 0.2, 3.0, false);
+        let mut later = Noise::Uniform.source(45, 0.2, 0.0, false);
+        assert_eq!(
+            phased.sample(2, 5, Attribute::XOffset, 1.0),
+            later.sample(2, 5, Attribute::XOffset, 1.0)
+        );
+    }
+}
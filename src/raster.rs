@@ -0,0 +1,160 @@
+//! Software rasterizer for PNG output, for machines without a GPU.
+//!
+//! It draws the same [`Scene`] as the nannou app's `view`, with the same
+//! miter joins and butt caps, so the two differ only in anti-aliasing.
+
+use std::{fs, io, path::Path};
+
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform};
+
+use crate::{metadata, scene, shape, BlendMode, Color, Layout, Scene, SchotterParams};
+
+/// The resolution of the layout's pixels: the window shows one per point.
+pub const BASE_DPI: f32 = 96.0;
+
+/// The most pixels an image may have, about 16k by 16k. Rendering holds two
+/// images of this size in memory at once.
+pub const MAX_PIXELS: u64 = 1 << 28;
+
+/// Rasterizes a composition at the layout's pixel size.
+///
+/// Every layer is drawn onto a transparent pixmap of its own and then
+/// composited onto the ones below with its blend mode.
+pub fn render(params: &SchotterParams) -> io::Result<Pixmap> {
+    let [width, height] = params.pixel_size();
+    render_canvas(
+        params,
+        width.ceil() as u32,
+        height.ceil() as u32,
+        [0.0, 0.0],
+    )
+}
+
+/// Rasterizes a composition onto a `width` by `height` canvas filled with the
+/// background, moved by `offset` pixels from where [`render`] puts it.
+///
+/// Fails if the canvas is empty or has more than [`MAX_PIXELS`].
+pub fn render_canvas(
+    params: &SchotterParams,
+    width: u32,
+    height: u32,
+    offset: [f32; 2],
+) -> io::Result<Pixmap> {
+    let layout = &params.layout;
+    let mut pixmap = canvas(width, height)?;
+    pixmap.fill(skia_color(layout.background, 1.0));
+    let transform = Transform::from_translate(offset[0], offset[1]);
+
+    for group in Scene::new(params).groups {
+        let mut layer = canvas(width, height)?;
+        draw_items(&mut layer, &group.items, layout, transform);
+        let paint = PixmapPaint {
+            blend_mode: skia_blend_mode(group.blend),
+            ..Default::default()
+        };
+        pixmap.draw_pixmap(0, 0, layer.as_ref(), &paint, Transform::identity(), None);
+    }
+
+    Ok(pixmap)
+}
+
+/// A transparent pixmap, if the size is one that can be rendered.
+fn canvas(width: u32, height: u32) -> io::Result<Pixmap> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot render a {} x {} pixel image", width, height),
+        )
+    };
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(invalid());
+    }
+    Pixmap::new(width, height).ok_or_else(invalid)
+}
+
+/// Rasterizes a composition at `dpi`, taking the layout's pixels to be
+/// [`BASE_DPI`] pixels. Strokes and margins scale with the stones, so the
+/// image looks the same at any resolution.
+pub fn render_at(params: &SchotterParams, dpi: f32) -> io::Result<Pixmap> {
+    let scale = dpi / BASE_DPI;
+    let mut scaled = params.clone();
+    scaled.layout.size *= scale;
+    scaled.layout.margin *= scale;
+    render(&scaled)
+}
+
+fn draw_items(pixmap: &mut Pixmap, items: &[scene::Item], layout: &Layout, transform: Transform) {
+    let stroke = Stroke {
+        width: layout.line_width * layout.size,
+        ..Default::default()
+    };
+
+    for item in items {
+        for path in &item.paths {
+            let Some(skia_path) = skia_path(path, layout) else {
+                continue;
+            };
+            if let (Some((fill, opacity)), true) = (item.paint.fill, path.closed) {
+                let paint = skia_paint(fill, opacity);
+                pixmap.fill_path(&skia_path, &paint, FillRule::Winding, transform, None);
+            }
+            let paint = skia_paint(item.paint.stroke, 1.0);
+            pixmap.stroke_path(&skia_path, &paint, &stroke, transform, None);
+        }
+    }
+}
+
+fn skia_blend_mode(blend: BlendMode) -> tiny_skia::BlendMode {
+    match blend {
+        BlendMode::Normal => tiny_skia::BlendMode::SourceOver,
+        BlendMode::Multiply => tiny_skia::BlendMode::Multiply,
+        BlendMode::Screen => tiny_skia::BlendMode::Screen,
+        BlendMode::Add => tiny_skia::BlendMode::Plus,
+        BlendMode::Darken => tiny_skia::BlendMode::Darken,
+        BlendMode::Lighten => tiny_skia::BlendMode::Lighten,
+    }
+}
+
+fn skia_color(Color([r, g, b]): Color, opacity: f32) -> tiny_skia::Color {
+    tiny_skia::Color::from_rgba8(r, g, b, (opacity * 255.0).round() as u8)
+}
+
+fn skia_paint(color: Color, opacity: f32) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(skia_color(color, opacity));
+    paint.anti_alias = true;
+    paint
+}
+
+/// Converts a path in grid units to a tiny-skia path in pixels.
+fn skia_path(path: &shape::Path, layout: &Layout) -> Option<tiny_skia::Path> {
+    let mut pb = PathBuilder::new();
+    for (i, &point) in path.points.iter().enumerate() {
+        let [x, y] = layout.to_pixels(point);
+        if i == 0 {
+            pb.move_to(x, y);
+        } else {
+            pb.line_to(x, y);
+        }
+    }
+    if path.closed {
+        pb.close();
+    }
+    pb.finish()
+}
+
+/// Encodes a composition at `dpi` as PNG bytes, with its parameters and
+/// resolution embedded.
+pub fn to_png(params: &SchotterParams, dpi: f32) -> io::Result<Vec<u8>> {
+    let png = render_at(params, dpi)?
+        .encode_png()
+        .map_err(io::Error::other)?;
+    let png = metadata::embed(&png, params).map_err(io::Error::other)?;
+    metadata::set_dpi(&png, dpi).map_err(io::Error::other)
+}
+
+/// Writes a composition to `path` as a PNG file at `dpi`. Does not need a
+/// window or a GPU.
+pub fn save<P: AsRef<Path>>(path: P, params: &SchotterParams, dpi: f32) -> io::Result<()> {
+    fs::write(path, to_png(params, dpi)?)
+}
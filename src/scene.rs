@@ -0,0 +1,87 @@
+//! A composition flattened into outlines, ready for any renderer.
+
+use crate::{occlusion, BlendMode, Paint, Path, SchotterParams, Stone};
+
+/// The outlines of one stone, in grid units, and their colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub col: u32,
+    pub row: u32,
+    pub paths: Vec<Path>,
+    pub paint: Paint,
+}
+
+/// The items of one visible layer, already moved by the layer's offset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    /// The index of the layer in [`SchotterParams::layers`].
+    pub layer: usize,
+    pub name: String,
+    pub blend: BlendMode,
+    pub items: Vec<Item>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    /// Bottom first.
+    pub groups: Vec<Group>,
+}
+
+impl Scene {
+    /// The finished composition.
+    pub fn new(params: &SchotterParams) -> Self {
+        let stones: Vec<_> = params
+            .layers
+            .iter()
+            .map(|layer| layer.schotter.stones())
+            .collect();
+        Scene::from_stones(params, &stones)
+    }
+
+    /// The composition with its stones wherever they currently are, e.g.
+    /// part way through an animation. `stones` holds one list per layer.
+    ///
+    /// Hidden lines are clipped as [`SchotterParams::occlusion`] asks.
+    pub fn from_stones(params: &SchotterParams, stones: &[Vec<Stone>]) -> Self {
+        let groups = params
+            .layers
+            .iter()
+            .zip(stones)
+            .enumerate()
+            .filter(|(_, (layer, _))| layer.visible)
+            .map(|(i, (layer, stones))| {
+                let schotter = &layer.schotter;
+                let [dx, dy] = layer.offset;
+                let items = stones
+                    .iter()
+                    .map(|stone| {
+                        let mut paths = stone.paths(schotter.shape_at(stone.col, stone.row));
+                        for point in paths.iter_mut().flat_map(|path| path.points.iter_mut()) {
+                            *point = [point[0] + dx, point[1] + dy];
+                        }
+                        Item {
+                            col: stone.col,
+                            row: stone.row,
+                            paths,
+                            paint: schotter.style.paint(stone, schotter),
+                        }
+                    })
+                    .collect();
+                Group {
+                    layer: i,
+                    name: layer.name.clone(),
+                    blend: layer.blend,
+                    items,
+                }
+            })
+            .collect();
+        let mut scene = Scene { groups };
+        occlusion::clip(&mut scene, params.occlusion);
+        scene
+    }
+
+    /// Every item, bottom layer first.
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.groups.iter().flat_map(|group| &group.items)
+    }
+}
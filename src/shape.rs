@@ -0,0 +1,239 @@
+//! Stone shapes, as outlines shared by the live view and every exporter.
+
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// A polyline in grid units. Closed paths join their last point to the first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Path {
+    pub points: Vec<[f32; 2]>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn closed(points: Vec<[f32; 2]>) -> Self {
+        Path {
+            points,
+            closed: true,
+        }
+    }
+
+    pub fn open(points: Vec<[f32; 2]>) -> Self {
+        Path {
+            points,
+            closed: false,
+        }
+    }
+}
+
+/// The outline drawn for each stone, in a unit cell centered on the origin.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoneShape {
+    #[default]
+    Square,
+    Circle,
+    Triangle,
+    /// A regular polygon with the given number of sides.
+    Polygon(u32),
+    Hexagon,
+    /// A horizontal line through the center.
+    Line,
+    Cross,
+    /// Any outline, with points in `-0.5..0.5`.
+    Polyline(Path),
+}
+
+impl StoneShape {
+    /// The most sides a [`StoneShape::Polygon`] is drawn with.
+    pub const MAX_SIDES: u32 = 1000;
+
+    /// One of each kind, for pickers.
+    pub fn examples() -> Vec<StoneShape> {
+        vec![
+            StoneShape::Square,
+            StoneShape::Circle,
+            StoneShape::Triangle,
+            StoneShape::Polygon(5),
+            StoneShape::Hexagon,
+            StoneShape::Line,
+            StoneShape::Cross,
+            StoneShape::Polyline(Path::open(vec![
+                [-0.5, 0.25],
+                [-0.25, -0.25],
+                [0.0, 0.25],
+                [0.25, -0.25],
+                [0.5, 0.25],
+            ])),
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StoneShape::Square => "square",
+            StoneShape::Circle => "circle",
+            StoneShape::Triangle => "triangle",
+            StoneShape::Polygon(_) => "polygon",
+            StoneShape::Hexagon => "hexagon",
+            StoneShape::Line => "line",
+            StoneShape::Cross => "cross",
+            StoneShape::Polyline(_) => "polyline",
+        }
+    }
+
+    /// The outline, before the stone is displaced and rotated.
+    pub fn paths(&self) -> Vec<Path> {
+        match self {
+            StoneShape::Square => vec![Path::closed(vec![
+                [-0.5, -0.5],
+                [0.5, -0.5],
+                [0.5, 0.5],
+                [-0.5, 0.5],
+            ])],
+            StoneShape::Circle => vec![regular_polygon(48)],
+            StoneShape::Triangle => vec![regular_polygon(3)],
+            StoneShape::Polygon(sides) => {
+                vec![regular_polygon((*sides).clamp(3, Self::MAX_SIDES))]
+            }
+            StoneShape::Hexagon => vec![regular_polygon(6)],
+            StoneShape::Line => vec![Path::open(vec![[-0.5, 0.0], [0.5, 0.0]])],
+            StoneShape::Cross => vec![
+                Path::open(vec![[-0.5, 0.0], [0.5, 0.0]]),
+                Path::open(vec![[0.0, -0.5], [0.0, 0.5]]),
+            ],
+            StoneShape::Polyline(path) => vec![path.clone()],
+        }
+    }
+}
+
+/// A polygon inscribed in the unit cell, with a vertex at the top.
+fn regular_polygon(sides: u32) -> Path {
+    Path::closed(
+        (0..sides)
+            .map(|i| {
+                let angle = -PI / 2.0 + i as f32 * 2.0 * PI / sides as f32;
+                [0.5 * angle.cos(), 0.5 * angle.sin()]
+            })
+            .collect(),
+    )
+}
+
+/// Formats shapes the way [`StoneShape::from_str`] reads them, e.g. `square`,
+/// `polygon:7` or `polyline:-0.5,0;0,-0.5;0.5,0;z`.
+impl fmt::Display for StoneShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoneShape::Polygon(sides) => write!(f, "polygon:{}", sides),
+            StoneShape::Polyline(path) => {
+                write!(f, "polyline:")?;
+                for (i, [x, y]) in path.points.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ";")?;
+                    }
+                    write!(f, "{},{}", x, y)?;
+                }
+                if path.closed {
+                    write!(f, ";z")?;
+                }
+                Ok(())
+            }
+            shape => write!(f, "{}", shape.name()),
+        }
+    }
+}
+
+impl FromStr for StoneShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid shape: {}", s);
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        match name {
+            "polygon" => match args.parse() {
+                Ok(sides) if (3..=StoneShape::MAX_SIDES).contains(&sides) => {
+                    Ok(StoneShape::Polygon(sides))
+                }
+                _ => Err(invalid()),
+            },
+            "polyline" => {
+                let mut path = Path::default();
+                for point in args.split(';') {
+                    if point == "z" {
+                        path.closed = true;
+                        continue;
+                    }
+                    let (x, y) = point.split_once(',').ok_or_else(invalid)?;
+                    let x = x.trim().parse().map_err(|_| invalid())?;
+                    let y = y.trim().parse().map_err(|_| invalid())?;
+                    path.points.push([x, y]);
+                }
+                if path.points.len() < 2 {
+                    return Err(invalid());
+                }
+                Ok(StoneShape::Polyline(path))
+            }
+            _ => StoneShape::examples()
+                .into_iter()
+                .find(|shape| shape.name() == name && args.is_empty())
+                .ok_or_else(invalid),
+        }
+    }
+}
+
+/// How shapes are assigned to stones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShapeMode {
+    /// Every stone uses the first shape.
+    #[default]
+    Global,
+    /// Rows cycle through the shapes.
+    Rows,
+    /// Columns cycle through the shapes.
+    Columns,
+}
+
+impl ShapeMode {
+    pub const ALL: [ShapeMode; 3] = [ShapeMode::Global, ShapeMode::Rows, ShapeMode::Columns];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShapeMode::Global => "global",
+            ShapeMode::Rows => "rows",
+            ShapeMode::Columns => "columns",
+        }
+    }
+}
+
+impl FromStr for ShapeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ShapeMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| format!("unknown shape mode: {}", s))
+    }
+}
+
+/// Parses a space separated list of shapes, e.g. `square circle polygon:5`.
+pub fn parse_shapes(s: &str) -> Result<Vec<StoneShape>, String> {
+    let shapes = s
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if shapes.is_empty() {
+        return Err(String::from("no shapes given"));
+    }
+    Ok(shapes)
+}
+
+/// Formats shapes the way [`parse_shapes`] reads them.
+pub fn format_shapes(shapes: &[StoneShape]) -> String {
+    shapes
+        .iter()
+        .map(|shape| shape.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
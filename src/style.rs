@@ -0,0 +1,252 @@
+//! Stroke and fill colors, and how they vary from stone to stone.
+
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{random, Attribute, Palette, Schotter, Stone};
+
+/// An sRGB color, written as `#rrggbb` in presets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 3]);
+
+impl Color {
+    pub const BLACK: Color = Color([0, 0, 0]);
+    pub const WHITESMOKE: Color = Color([245, 245, 245]);
+
+    /// Mixes two colors, `t` going from 0 (all `self`) to 1 (all `other`).
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let mut mixed = [0; 3];
+        for (i, channel) in mixed.iter_mut().enumerate() {
+            let a = self.0[i] as f32;
+            let b = other.0[i] as f32;
+            *channel = (a + (b - a) * t).round() as u8;
+        }
+        Color(mixed)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// Parses `#rrggbb`, `rrggbb` or the short `#rgb` form.
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid color: {}", s);
+        let hex = s.trim().trim_start_matches('#');
+        let hex = match hex.len() {
+            3 => hex.chars().flat_map(|c| [c, c]).collect(),
+            6 => hex.to_string(),
+            _ => return Err(invalid()),
+        };
+        let mut rgb = [0; 3];
+        for (i, channel) in rgb.iter_mut().enumerate() {
+            let digits = hex.get(2 * i..2 * i + 2).ok_or_else(invalid)?;
+            *channel = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        }
+        Ok(Color(rgb))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+/// What picks a stone's color from [`Style::palette`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    /// Every stone uses the plain stroke and fill colors.
+    #[default]
+    Solid,
+    /// A gradient from the top row to the bottom one.
+    Row,
+    /// A gradient from the left column to the right one.
+    Column,
+    /// A gradient from the calmest stone to the most displaced one.
+    Displacement,
+    /// A gradient from unrotated stones to the most rotated ones.
+    Rotation,
+    /// A random pick for every stone, from the seed.
+    Random,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 6] = [
+        ColorMode::Solid,
+        ColorMode::Row,
+        ColorMode::Column,
+        ColorMode::Displacement,
+        ColorMode::Rotation,
+        ColorMode::Random,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMode::Solid => "solid",
+            ColorMode::Row => "row",
+            ColorMode::Column => "column",
+            ColorMode::Displacement => "displacement",
+            ColorMode::Rotation => "rotation",
+            ColorMode::Random => "random",
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ColorMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| format!("unknown color mode: {}", s))
+    }
+}
+
+/// Which part of a stone the color mode paints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorTarget {
+    #[default]
+    Stroke,
+    Fill,
+    Both,
+}
+
+impl ColorTarget {
+    pub const ALL: [ColorTarget; 3] = [ColorTarget::Stroke, ColorTarget::Fill, ColorTarget::Both];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorTarget::Stroke => "stroke",
+            ColorTarget::Fill => "fill",
+            ColorTarget::Both => "both",
+        }
+    }
+}
+
+impl FromStr for ColorTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ColorTarget::ALL
+            .into_iter()
+            .find(|target| target.name() == s)
+            .ok_or_else(|| format!("unknown color target: {}", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub stroke: Color,
+    pub fill: Color,
+    /// From 0 (no fill, as in Nees' original) to 1 (opaque).
+    pub fill_opacity: f32,
+    pub color_mode: ColorMode,
+    pub color_target: ColorTarget,
+    /// The gradient stops, or the choices for [`ColorMode::Random`].
+    #[serde(alias = "colors")]
+    pub palette: Palette,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            stroke: Color::BLACK,
+            fill: Color::BLACK,
+            fill_opacity: 0.0,
+            color_mode: ColorMode::default(),
+            color_target: ColorTarget::default(),
+            palette: Palette::default(),
+        }
+    }
+}
+
+/// The resolved colors of one stone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Paint {
+    pub stroke: Color,
+    /// The fill color and its opacity, if the stone is filled at all.
+    pub fill: Option<(Color, f32)>,
+}
+
+impl Style {
+    pub fn paint(&self, stone: &Stone, schotter: &Schotter) -> Paint {
+        let mapped = self.mapped_color(stone, schotter);
+        let stroke = match (mapped, self.color_target) {
+            (Some(color), ColorTarget::Stroke | ColorTarget::Both) => color,
+            _ => self.stroke,
+        };
+        let fill = match (mapped, self.color_target) {
+            (Some(color), ColorTarget::Fill | ColorTarget::Both) => color,
+            _ => self.fill,
+        };
+        Paint {
+            stroke,
+            fill: (self.fill_opacity > 0.0).then_some((fill, self.fill_opacity.min(1.0))),
+        }
+    }
+
+    /// The color picked by the color mode, if it picks one.
+    fn mapped_color(&self, stone: &Stone, schotter: &Schotter) -> Option<Color> {
+        let colors = &self.palette.colors;
+        if colors.is_empty() {
+            return None;
+        }
+        let t = match self.color_mode {
+            ColorMode::Solid => return None,
+            ColorMode::Row => stone.row as f32 / (schotter.rows.max(2) - 1) as f32,
+            ColorMode::Column => stone.col as f32 / (schotter.cols.max(2) - 1) as f32,
+            ColorMode::Displacement => {
+                let max = 0.5 * 2f32.sqrt() * schotter.disp_adj;
+                ratio(stone.x_offset.hypot(stone.y_offset), max)
+            }
+            ColorMode::Rotation => ratio(stone.rotation.abs(), PI / 4.0 * schotter.rot_adj),
+            ColorMode::Random => {
+                let hash =
+                    random::stone_hash(schotter.seed, stone.col, stone.row, Attribute::Color);
+                let index = (random::unit(hash) * colors.len() as f32) as usize;
+                return Some(colors[index.min(colors.len() - 1)]);
+            }
+        };
+        Some(gradient(colors, t))
+    }
+}
+
+fn ratio(value: f32, max: f32) -> f32 {
+    if max > 0.0 {
+        (value / max).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Samples evenly spaced gradient stops at `t` in `0.0..=1.0`.
+fn gradient(stops: &[Color], t: f32) -> Color {
+    if stops.len() == 1 {
+        return stops[0];
+    }
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (position.floor() as usize).min(stops.len() - 2);
+    stops[i].lerp(stops[i + 1], position - i as f32)
+}
@@ -0,0 +1,91 @@
+//! SVG export for pen plotters and large prints.
+
+use std::{fmt::Write, fs, io, path::Path};
+
+use crate::{shape, BlendMode, Layout, Scene, SchotterParams};
+
+/// Renders a composition as an SVG document using the same geometry as the
+/// nannou app's `view`.
+///
+/// Each layer becomes a group that Inkscape, and plotter tools built on it,
+/// treat as a layer of its own.
+pub fn to_svg(params: &SchotterParams) -> String {
+    let layout = &params.layout;
+    let [width, height] = params.pixel_size();
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        layout.background
+    )
+    .unwrap();
+
+    for group in Scene::new(params).groups {
+        write!(
+            svg,
+            r#"<g id="layer{}" inkscape:groupmode="layer" inkscape:label="{}" fill="none" stroke-width="{}" stroke-linejoin="miter""#,
+            group.layer + 1,
+            escape(&group.name),
+            layout.line_width * layout.size
+        )
+        .unwrap();
+        if group.blend != BlendMode::Normal {
+            write!(svg, r#" style="mix-blend-mode:{}""#, group.blend.css()).unwrap();
+        }
+        svg.push_str(">\n");
+
+        for item in &group.items {
+            for path in &item.paths {
+                write!(
+                    svg,
+                    r#"<path d="{}" stroke="{}""#,
+                    path_data(path, layout),
+                    item.paint.stroke
+                )
+                .unwrap();
+                if let (Some((fill, opacity)), true) = (item.paint.fill, path.closed) {
+                    write!(svg, r#" fill="{}" fill-opacity="{}""#, fill, opacity).unwrap();
+                }
+                svg.push_str("/>\n");
+            }
+        }
+        svg.push_str("</g>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes a composition to `path` as an SVG file. Does not need a window.
+pub fn save<P: AsRef<Path>>(path: P, params: &SchotterParams) -> io::Result<()> {
+    fs::write(path, to_svg(params))
+}
+
+/// The `d` attribute of a path, in pixels.
+fn path_data(path: &shape::Path, layout: &Layout) -> String {
+    let mut d = String::new();
+    for (i, &point) in path.points.iter().enumerate() {
+        let [x, y] = layout.to_pixels(point);
+        write!(d, "{}{:.3},{:.3} ", if i == 0 { "M" } else { "L" }, x, y).unwrap();
+    }
+    if path.closed {
+        d.push('Z');
+    }
+    d.trim_end().to_string()
+}
+
+/// Escapes text for use in an attribute value.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
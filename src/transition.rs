@@ -0,0 +1,309 @@
+//! Time-based motion of the stones: the intro fly-in, and the morph from one
+//! layout to another when the seed or the factors change.
+//!
+//! A [`Transition`] only remembers where the stones were and where they are
+//! going, so the stones at any moment depend on the clock alone and move at
+//! the same speed at any frame rate.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    random::{stone_hash, unit, Attribute},
+    Easing, Stone,
+};
+
+/// The order in which stones set off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stagger {
+    /// All at once.
+    None,
+    /// Top row first.
+    #[default]
+    Row,
+    /// Left column first.
+    Column,
+    /// From the center of the grid outwards.
+    Distance,
+    Random,
+}
+
+impl Stagger {
+    pub const ALL: [Stagger; 5] = [
+        Stagger::None,
+        Stagger::Row,
+        Stagger::Column,
+        Stagger::Distance,
+        Stagger::Random,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stagger::None => "none",
+            Stagger::Row => "row",
+            Stagger::Column => "column",
+            Stagger::Distance => "distance",
+            Stagger::Random => "random",
+        }
+    }
+
+    /// How late the stone at `col`, `row` of a `cols` by `rows` grid sets
+    /// off, from 0 for the first to 1 for the last.
+    pub fn delay(self, col: u32, row: u32, cols: u32, rows: u32) -> f32 {
+        let fraction = |i: u32, n: u32| {
+            if n > 1 {
+                i as f32 / (n - 1) as f32
+            } else {
+                0.0
+            }
+        };
+        match self {
+            Stagger::None => 0.0,
+            Stagger::Row => fraction(row, rows),
+            Stagger::Column => fraction(col, cols),
+            Stagger::Distance => {
+                let dx = (col as f32 + 0.5) / cols.max(1) as f32 * 2.0 - 1.0;
+                let dy = (row as f32 + 0.5) / rows.max(1) as f32 * 2.0 - 1.0;
+                ((dx * dx + dy * dy) / 2.0).sqrt()
+            }
+            Stagger::Random => unit(stone_hash(0, col, row, Attribute::Stagger)),
+        }
+    }
+}
+
+impl FromStr for Stagger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Stagger::ALL
+            .into_iter()
+            .find(|stagger| stagger.name() == s)
+            .ok_or_else(|| format!("unknown stagger: {}", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransitionSettings {
+    pub easing: Easing,
+    pub stagger: Stagger,
+    /// From the first stone setting off to the last one arriving, in seconds.
+    pub duration: f32,
+    /// The share of the duration over which stones set off, one after
+    /// another; the rest is each stone's own journey.
+    pub spread: f32,
+    /// Tween each stone's offsets and rotation when the seed or the factors
+    /// change, instead of jumping to the new values.
+    pub morph: bool,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        TransitionSettings {
+            easing: Easing::default(),
+            stagger: Stagger::default(),
+            duration: 1.5,
+            spread: 0.5,
+            morph: true,
+        }
+    }
+}
+
+impl TransitionSettings {
+    /// How far along a stone that sets off `delay` late is, `elapsed`
+    /// seconds in, before easing.
+    fn progress(&self, elapsed: f32, delay: f32) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        let spread = match self.stagger {
+            Stagger::None => 0.0,
+            _ => self.spread.clamp(0.0, 0.99),
+        };
+        ((elapsed / self.duration - delay * spread) / (1.0 - spread)).clamp(0.0, 1.0)
+    }
+}
+
+/// Stones on their way from one layout to another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub from: Vec<Stone>,
+    pub to: Vec<Stone>,
+    /// When the stones set off, in seconds on the caller's clock.
+    pub start: f32,
+}
+
+impl Transition {
+    /// Stones that are already where they are going.
+    pub fn still(stones: Vec<Stone>) -> Self {
+        Transition {
+            from: stones.clone(),
+            to: stones,
+            start: f32::NEG_INFINITY,
+        }
+    }
+
+    /// The intro: every stone starts square and upright at the top left
+    /// corner.
+    pub fn fly_in(to: Vec<Stone>, start: f32) -> Self {
+        let from = to
+            .iter()
+            .map(|stone| Stone {
+                x: 0.0,
+                y: 0.0,
+                x_offset: 0.0,
+                y_offset: 0.0,
+                rotation: 0.0,
+                ..*stone
+            })
+            .collect();
+        Transition { from, to, start }
+    }
+
+    /// Sends the stones to `to` from wherever they are at `time`, or puts
+    /// them straight there if `settings` does not morph. Stones of a
+    /// different grid fly in instead.
+    pub fn retarget(&mut self, to: Vec<Stone>, time: f32, settings: &TransitionSettings) {
+        if self.to == to {
+            return;
+        }
+        let same_grid = self.to.len() == to.len()
+            && self
+                .to
+                .iter()
+                .zip(&to)
+                .all(|(a, b)| (a.col, a.row) == (b.col, b.row));
+        *self = if same_grid && !settings.morph {
+            Transition::still(to)
+        } else if same_grid {
+            Transition {
+                from: self.stones_at(time, settings),
+                to,
+                start: time,
+            }
+        } else {
+            Transition::fly_in(to, time)
+        };
+    }
+
+    /// Where the stones are at `time`.
+    pub fn stones_at(&self, time: f32, settings: &TransitionSettings) -> Vec<Stone> {
+        let cols = self.to.iter().map(|stone| stone.col + 1).max().unwrap_or(0);
+        let rows = self.to.iter().map(|stone| stone.row + 1).max().unwrap_or(0);
+        let elapsed = time - self.start;
+        self.from
+            .iter()
+            .zip(&self.to)
+            .map(|(from, to)| {
+                let delay = settings.stagger.delay(to.col, to.row, cols, rows);
+                let t = settings.easing.apply(settings.progress(elapsed, delay));
+                // Exact at both ends, so stones land right where they go.
+                let lerp = |a: f32, b: f32| a * (1.0 - t) + b * t;
+                Stone {
+                    x: lerp(from.x, to.x),
+                    y: lerp(from.y, to.y),
+                    x_offset: lerp(from.x_offset, to.x_offset),
+                    y_offset: lerp(from.y_offset, to.y_offset),
+                    rotation: lerp(from.rotation, to.rotation),
+                    ..*to
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Schotter;
+
+    fn stones(seed: u64) -> Vec<Stone> {
+        Schotter {
+            cols: 3,
+            rows: 4,
+            ..Schotter::new(seed)
+        }
+        .stones()
+    }
+
+    #[test]
+    fn transitions_start_and_end_exactly() {
+        for easing in Easing::ALL {
+            for stagger in Stagger::ALL {
+                let settings = TransitionSettings {
+                    easing,
+                    stagger,
+                    ..Default::default()
+                };
+                let transition = Transition {
+                    from: stones(1),
+                    to: stones(2),
+                    start: 10.0,
+                };
+                let name = (easing.name(), stagger.name());
+                assert_eq!(
+                    transition.stones_at(10.0, &settings),
+                    stones(1),
+                    "{:?}",
+                    name
+                );
+                let end = 10.0 + settings.duration;
+                assert_eq!(
+                    transition.stones_at(end, &settings),
+                    stones(2),
+                    "{:?}",
+                    name
+                );
+                assert_eq!(
+                    transition.stones_at(end + 5.0, &settings),
+                    stones(2),
+                    "{:?}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn new_seeds_morph_from_where_the_stones_are() {
+        let settings = TransitionSettings::default();
+        let mut transition = Transition::still(stones(1));
+        transition.retarget(stones(2), 3.0, &settings);
+        assert_eq!(transition.from, stones(1));
+        assert_eq!(transition.to, stones(2));
+        assert_eq!(transition.start, 3.0);
+
+        // Changing the seed again halfway sets off from the stones in flight.
+        let halfway = transition.stones_at(3.75, &settings);
+        assert_ne!(halfway, stones(1));
+        transition.retarget(stones(3), 3.75, &settings);
+        assert_eq!(transition.from, halfway);
+        assert_eq!(transition.stones_at(3.75, &settings), halfway);
+
+        // Retargeting where the stones already go keeps them on their way.
+        transition.retarget(stones(3), 4.0, &settings);
+        assert_eq!(transition.start, 3.75);
+    }
+
+    #[test]
+    fn new_seeds_jump_without_morphing() {
+        let settings = TransitionSettings {
+            morph: false,
+            ..Default::default()
+        };
+        let mut transition = Transition::still(stones(1));
+        transition.retarget(stones(2), 3.0, &settings);
+        assert_eq!(transition.stones_at(3.0, &settings), stones(2));
+    }
+
+    #[test]
+    fn new_grids_fly_in() {
+        let settings = TransitionSettings::default();
+        let mut transition = Transition::still(stones(1));
+        let to = Schotter::new(1).stones();
+        transition.retarget(to.clone(), 3.0, &settings);
+        assert_eq!(transition, Transition::fly_in(to, 3.0));
+    }
+}
@@ -0,0 +1,216 @@
+//! Offline rendering of an [`Animation`] to a PNG sequence, an animated GIF,
+//! an APNG, or an MP4 through a local `ffmpeg`.
+//!
+//! Frames are rasterized on the CPU like [`raster`] PNGs, so clips can be
+//! made without a window.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use tiny_skia::Pixmap;
+
+use crate::{raster, Animation, SchotterParams};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoFormat {
+    /// Numbered PNG files in a directory, each with its parameters embedded.
+    Frames,
+    #[default]
+    Gif,
+    Apng,
+    /// H.264, encoded by `ffmpeg`.
+    Mp4,
+}
+
+impl VideoFormat {
+    pub const ALL: [VideoFormat; 4] = [
+        VideoFormat::Frames,
+        VideoFormat::Gif,
+        VideoFormat::Apng,
+        VideoFormat::Mp4,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VideoFormat::Frames => "frames",
+            VideoFormat::Gif => "gif",
+            VideoFormat::Apng => "apng",
+            VideoFormat::Mp4 => "mp4",
+        }
+    }
+
+    /// The file extension, or none for a directory of frames.
+    pub fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Frames => "",
+            VideoFormat::Gif => ".gif",
+            VideoFormat::Apng => ".apng",
+            VideoFormat::Mp4 => ".mp4",
+        }
+    }
+}
+
+impl FromStr for VideoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VideoFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| format!("unknown video format: {}", s))
+    }
+}
+
+/// Whether an `ffmpeg` binary is on the `PATH`, for [`VideoFormat::Mp4`].
+pub fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Renders every frame of `animation`, applied to the layer at index
+/// `layer`, at `dpi` and writes the clip to `path`. For
+/// [`VideoFormat::Frames`], `path` is a directory, created if missing.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    format: VideoFormat,
+    params: &SchotterParams,
+    layer: usize,
+    animation: &Animation,
+    dpi: f32,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    if format == VideoFormat::Frames {
+        fs::create_dir_all(path)?;
+        for (i, frame) in animation.frames(params, layer).enumerate() {
+            let png = raster::to_png(&frame, dpi)?;
+            fs::write(path.join(format!("frame-{:04}.png", i)), png)?;
+        }
+        return Ok(());
+    }
+
+    let mut frames = animation.frames(params, layer);
+    let Some(first) = frames.next() else {
+        return Ok(());
+    };
+    let first = raster::render_at(&first, dpi)?;
+    let (width, height) = (first.width(), first.height());
+    let pixmaps =
+        std::iter::once(Ok(first)).chain(frames.map(|frame| raster::render_at(&frame, dpi)));
+    match format {
+        VideoFormat::Frames => unreachable!(),
+        VideoFormat::Gif => write_gif(path, width, height, pixmaps, animation),
+        VideoFormat::Apng => write_apng(path, width, height, pixmaps, animation),
+        VideoFormat::Mp4 => write_mp4(path, width, height, pixmaps, animation),
+    }
+}
+
+fn write_gif(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixmaps: impl Iterator<Item = io::Result<Pixmap>>,
+    animation: &Animation,
+) -> io::Result<()> {
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(io::Error::other("too large for a GIF"));
+    };
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(io::Error::other)?;
+    if animation.looping {
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+    }
+    // GIF delays are in hundredths of a second.
+    let delay = (100.0 / animation.fps).round().max(1.0) as u16;
+    for pixmap in pixmaps {
+        let mut pixmap = pixmap?;
+        let mut frame = gif::Frame::from_rgba_speed(width, height, pixmap.data_mut(), 10);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+fn write_apng(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixmaps: impl Iterator<Item = io::Result<Pixmap>>,
+    animation: &Animation,
+) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let plays = if animation.looping { 0 } else { 1 };
+    encoder
+        .set_animated(animation.frame_count(), plays)
+        .map_err(io::Error::other)?;
+    let denominator = (animation.fps * 100.0).round().clamp(1.0, u16::MAX as f32) as u16;
+    encoder
+        .set_frame_delay(100, denominator)
+        .map_err(io::Error::other)?;
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    // The background is opaque, so premultiplied and straight alpha agree.
+    for pixmap in pixmaps {
+        writer
+            .write_image_data(pixmap?.data())
+            .map_err(io::Error::other)?;
+    }
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Pipes raw frames into `ffmpeg`, which pads them to even sizes as H.264
+/// requires.
+fn write_mp4(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixmaps: impl Iterator<Item = io::Result<Pixmap>>,
+    animation: &Animation,
+) -> io::Result<()> {
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &animation.fps.to_string(), "-i", "-"])
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => io::Error::new(err.kind(), "ffmpeg not found"),
+            _ => err,
+        })?;
+    let mut stdin = ffmpeg.stdin.take().unwrap();
+    for pixmap in pixmaps {
+        stdin.write_all(pixmap?.data())?;
+    }
+    drop(stdin);
+    let status = ffmpeg.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("ffmpeg failed: {}", status)));
+    }
+    Ok(())
+}